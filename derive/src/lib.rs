@@ -6,7 +6,7 @@
 extern crate proc_macro;
 extern crate proc_macro2;
 use proc_macro2::{Span, TokenStream};
-use quote::quote;
+use quote::{quote, quote_spanned};
 use std::env;
 use std::fs;
 use std::io;
@@ -14,49 +14,16 @@ use std::io::Read;
 use std::path::Path;
 use syn::{Ident, LitStr};
 
-mod spec;
+mod predicate;
 
-use crate::spec::{SpecFormat, SpecPriority, SpecTemplate};
-
-fn check_template(template: &SpecTemplate) -> (Ident, Vec<LitStr>, Ident, LitStr) {
-    let first_uppercase = template
-        .identifier
-        .chars()
-        .next()
-        .map(|c| c.is_uppercase())
-        .unwrap_or(false);
-
-    if !first_uppercase {
-        panic!(
-            "first character of identifier {:?} \
-             should be uppercase!",
-            template.identifier
-        );
-    }
-
-    if template.names.is_empty() {
-        panic!(
-            "{:?}: templates must have at least one name!",
-            template.identifier
-        );
-    }
-
-    for child in &template.attributes {
-        if child.identifier.chars().any(|c| c.is_uppercase()) {
-            panic!(
-                "{:?}: attribute identifiers should be lowercase!",
-                child.identifier
-            )
-        }
-
-        if child.names.is_empty() {
-            panic!(
-                "{:?}: attributes must have at least one name!",
-                child.identifier
-            );
-        }
-    }
+use crate::predicate::{parse_predicate, PredicateCode};
+use mwparser_utils_meta::check::boolean_value;
+use mwparser_utils_meta::{
+    check_spec, json_schema, load_spec, reference_html, reference_markdown, sort_errors,
+    SpecAttribute, SpecError, SpecFormat, SpecPosition, SpecPriority, SpecTemplate, SpecType,
+};
 
+fn template_idents(template: &SpecTemplate) -> (Ident, Vec<LitStr>, Ident, LitStr) {
     let name: Ident = Ident::new(&template.identifier, Span::call_site());
     let names = str_to_lower_lit(&template.names);
     let format = match template.format {
@@ -72,7 +39,7 @@ fn implement_template_id(templates: &[SpecTemplate]) -> TokenStream {
     let variants: Vec<Ident> = templates
         .iter()
         .map(|template| {
-            let (name, _, _, _) = check_template(template);
+            let (name, _, _, _) = template_idents(template);
             name
         })
         .collect();
@@ -85,6 +52,8 @@ fn implement_template_id(templates: &[SpecTemplate]) -> TokenStream {
     let dsc_variants = variants.iter();
     let names_variants = variants.iter();
     let p_variants = variants.iter();
+    let u_variants = variants.iter();
+    let t_variants = variants.iter();
 
     quote! {
         /// The available template types.
@@ -114,6 +83,22 @@ fn implement_template_id(templates: &[SpecTemplate]) -> TokenStream {
                     #( KnownTemplate::#p_variants(ref t) => &t.present ),*
                 }
             }
+            /// Arguments which do not belong to any attribute, possibly misspelled.
+            pub fn unknown(&self) -> &Vec<UnknownArgument<'e>> {
+                match *self {
+                    #( KnownTemplate::#u_variants(ref t) => &t.unknown ),*
+                }
+            }
+            /// Rebuilds a template element, see the `to_template` of the variants.
+            pub fn to_template(&self) -> Element {
+                match *self {
+                    #( KnownTemplate::#t_variants(ref t) => t.to_template() ),*
+                }
+            }
+            /// Serializes this template to wikitext.
+            pub fn to_wikitext(&self) -> String {
+                to_wikitext(std::slice::from_ref(&self.to_template()))
+            }
             pub fn find(&self, name: &str) -> Option<&Attribute<'e>> {
                 for attribute in self.present() {
                     if &attribute.name == name {
@@ -133,6 +118,54 @@ fn str_to_lower_lit(input: &[String]) -> Vec<LitStr> {
         .collect()
 }
 
+fn str_to_lit(input: &[String]) -> Vec<LitStr> {
+    input
+        .iter()
+        .map(|a| LitStr::new(a, Span::call_site()))
+        .collect()
+}
+
+/// Names of an attribute for the spec. Purely positional attributes are named by their position.
+fn attribute_names(attribute: &SpecAttribute) -> Vec<LitStr> {
+    match attribute.position {
+        Some(position) if attribute.names.is_empty() => {
+            vec![LitStr::new(&position.to_string(), Span::call_site())]
+        }
+        _ => str_to_lower_lit(&attribute.names),
+    }
+}
+
+/// Names and deprecated names of an attribute, without its position.
+fn accepted_names(attribute: &SpecAttribute) -> Vec<LitStr> {
+    attribute
+        .accepted_names()
+        .iter()
+        .map(|n| LitStr::new(n, Span::call_site()))
+        .collect()
+}
+
+fn option_to_tokens<T: quote::ToTokens>(value: Option<T>) -> TokenStream {
+    match value {
+        Some(value) => quote! { Some(#value) },
+        None => quote! { None },
+    }
+}
+
+/// An expression finding the template argument for an attribute,
+/// or all arguments of a repeated attribute.
+fn find_attribute(attribute: &SpecAttribute) -> TokenStream {
+    let names = accepted_names(attribute);
+    if attribute.repeated {
+        return quote! {
+            find_repeated_arguments(&[ #( #names.into() ),* ])
+        };
+    }
+    let position = option_to_tokens(attribute.position);
+    quote! {
+        find_argument(&[ #( #names.into() ),* ], #position)
+    }
+}
+
 fn priority_to_ident(prio: SpecPriority) -> Ident {
     match prio {
         SpecPriority::Required => Ident::new("Required", Span::call_site()),
@@ -140,23 +173,167 @@ fn priority_to_ident(prio: SpecPriority) -> Ident {
     }
 }
 
+fn type_to_ident(value_type: SpecType) -> Ident {
+    let name = match value_type {
+        SpecType::Elements => "Elements",
+        SpecType::Text => "Text",
+        SpecType::Integer => "Integer",
+        SpecType::Boolean => "Boolean",
+        SpecType::Enum => "Enum",
+        SpecType::Formula => "Formula",
+        SpecType::File => "File",
+    };
+    Ident::new(name, Span::call_site())
+}
+
+/// The type of the struct field generated for an attribute.
+fn attribute_field_type(attribute: &SpecAttribute) -> TokenStream {
+    match attribute.value_type {
+        SpecType::Elements => quote! { &'e [Element] },
+        SpecType::Integer => quote! { i64 },
+        SpecType::Boolean => quote! { bool },
+        SpecType::Text | SpecType::Enum | SpecType::Formula | SpecType::File => {
+            quote! { String }
+        }
+    }
+}
+
+/// An expression parsing `content` to a `Result` of the attribute type.
+/// Returns `None` for attributes which need no conversion.
+fn attribute_parser(attribute: &SpecAttribute, content: TokenStream) -> Option<TokenStream> {
+    Some(match attribute.value_type {
+        SpecType::Elements => return None,
+        SpecType::Text => quote! { parse_text(#content) },
+        SpecType::Integer => quote! { parse_integer(#content) },
+        SpecType::Boolean => quote! { parse_boolean(#content) },
+        SpecType::Formula => quote! { parse_formula(#content) },
+        SpecType::File => quote! { parse_file_name(#content) },
+        SpecType::Enum => {
+            let values = str_to_lower_lit(&attribute.values);
+            quote! { parse_enum(#content, &[ #( #values ),* ]) }
+        }
+    })
+}
+
+/// An expression converting the template argument `arg` to the attribute type.
+/// Returns `None` for attributes which need no conversion.
+fn attribute_conversion(template: &SpecTemplate, attribute: &SpecAttribute) -> Option<TokenStream> {
+    let conversion = attribute_parser(attribute, quote! { &arg.value })?;
+    let template_id = LitStr::new(&template.identifier, Span::call_site());
+    let identifier = LitStr::new(&attribute.identifier, Span::call_site());
+    let value_type = type_to_ident(attribute.value_type);
+    Some(quote! {
+        #conversion.map_err(|cause| ParseError::Conversion {
+            template: #template_id.into(),
+            position: template.position.clone(),
+            error: ConversionError {
+                attribute: #identifier.into(),
+                expected: AttributeType::#value_type,
+                position: arg.position.clone(),
+                cause,
+            },
+        })?
+    })
+}
+
+/// An expression for the default value of an attribute, if it has one.
+fn attribute_default(attribute: &SpecAttribute) -> Option<TokenStream> {
+    let default = attribute.default.as_ref()?;
+    let value = default.trim();
+    Some(match attribute.value_type {
+        // `check_default` made sure the default can be parsed.
+        SpecType::Elements => quote! {{
+            static DEFAULT: std::sync::OnceLock<Vec<Element>> = std::sync::OnceLock::new();
+            DEFAULT.get_or_init(|| parse_fragment(#default)).as_slice()
+        }},
+        SpecType::Integer => {
+            let value = value.parse::<i64>().unwrap_or_default();
+            quote! { #value }
+        }
+        SpecType::Boolean => {
+            let value = boolean_value(value).unwrap_or_default();
+            quote! { #value }
+        }
+        SpecType::Enum => {
+            let value = value.to_lowercase();
+            quote! { #value.into() }
+        }
+        SpecType::Text | SpecType::Formula | SpecType::File => quote! { #value.into() },
+    })
+}
+
+/// Name of the function generated for a predicate with arguments.
+fn predicate_fn_name(template: &SpecTemplate, attribute: &SpecAttribute) -> Ident {
+    let name = format!(
+        "spec_predicate_{}_{}",
+        template.identifier.to_lowercase(),
+        attribute.identifier
+    );
+    Ident::new(&name, Span::call_site())
+}
+
+/// An expression for the predicate function of an attribute.
+fn predicate_expr(template: &SpecTemplate, attribute: &SpecAttribute) -> TokenStream {
+    parse_predicate(&attribute.predicate)
+        .expect("predicate should be checked!")
+        .function(&predicate_fn_name(template, attribute))
+}
+
+/// Functions for predicates with arguments.
+fn implement_predicate_fns(templates: &[SpecTemplate]) -> Vec<TokenStream> {
+    let mut functions = vec![];
+    for template in templates {
+        for attribute in &template.attributes {
+            let predicate =
+                parse_predicate(&attribute.predicate).expect("predicate should be checked!");
+            if predicate.is_call() {
+                functions.push(predicate.implement(&predicate_fn_name(template, attribute)));
+            }
+        }
+    }
+    functions
+}
+
 fn implement_attribute_spec(template: &SpecTemplate) -> Vec<TokenStream> {
     template
         .attributes
         .iter()
         .map(|attribute| {
-            let names = str_to_lower_lit(&attribute.names);
+            let names = attribute_names(attribute);
+            let deprecated_names = str_to_lower_lit(&attribute.deprecated_names);
             let priority = priority_to_ident(attribute.priority);
-            let predicate = Ident::new(&attribute.predicate, Span::call_site());
+            let predicate = predicate_expr(template, attribute);
             let description = LitStr::new(&attribute.description, Span::call_site());
-            let pred_name = LitStr::new(&attribute.predicate, Span::call_site());
+            let pred_name = LitStr::new(attribute.predicate.trim(), Span::call_site());
+            let value_type = type_to_ident(attribute.value_type);
+            let values = str_to_lower_lit(&attribute.values);
+            let position = option_to_tokens(attribute.position);
+            let default =
+                option_to_tokens(attribute.default.as_ref().map(|d| quote! { #d.into() }));
+            let repeated = attribute.repeated;
+            let min_count = attribute.min_count();
+            let max_count = option_to_tokens(attribute.max_count);
+            let identifier = LitStr::new(&attribute.identifier, Span::call_site());
+            let requires = str_to_lit(&attribute.requires);
+            let conflicts_with = str_to_lit(&attribute.conflicts_with);
             quote! {
                 AttributeSpec {
+                    identifier: #identifier.into(),
                     names: vec![ #( #names.into() ),*],
+                    deprecated_names: vec![ #( #deprecated_names.into() ),* ],
                     priority: Priority::#priority,
                     predicate: &#predicate,
                     predicate_name: #pred_name.into(),
                     description: #description.into(),
+                    value_type: AttributeType::#value_type,
+                    values: vec![ #( #values.into() ),* ],
+                    position: #position,
+                    default: #default,
+                    repeated: #repeated,
+                    min_count: #min_count,
+                    max_count: #max_count,
+                    requires: vec![ #( #requires.into() ),* ],
+                    conflicts_with: vec![ #( #conflicts_with.into() ),* ],
                 }
             }
         })
@@ -165,13 +342,41 @@ fn implement_attribute_spec(template: &SpecTemplate) -> Vec<TokenStream> {
 
 fn implement_spec_list(templates: &[SpecTemplate]) -> TokenStream {
     let specs = templates.iter().map(|template| {
-        let (_, names, format, description) = check_template(template);
+        let (_, names, format, description) = template_idents(template);
         let attributes = implement_attribute_spec(template);
+        let identifier = LitStr::new(&template.identifier, Span::call_site());
+        let deprecated_names = str_to_lower_lit(&template.deprecated_names);
+        let allowed_in = str_to_lit(&template.allowed_in);
+        let forbidden_in = str_to_lit(&template.forbidden_in);
+        let one_of = template.one_of.iter().map(|group| {
+            let group = str_to_lit(group);
+            quote! { vec![ #( #group.into() ),* ] }
+        });
+        let allowed_children = match template.allowed_children {
+            Some(ref children) => {
+                let children = str_to_lit(children);
+                quote! { Some(vec![ #( #children.into() ),* ]) }
+            }
+            None => quote! { None },
+        };
+        let max_per_document = option_to_tokens(template.max_per_document);
+        let position = option_to_tokens(template.position.map(|position| match position {
+            SpecPosition::First => quote! { DocumentPosition::First },
+            SpecPosition::Last => quote! { DocumentPosition::Last },
+        }));
         quote! {
             TemplateSpec {
+                identifier: #identifier.into(),
                 names: vec![ #( #names.into() ),* ],
+                deprecated_names: vec![ #( #deprecated_names.into() ),* ],
                 description: #description.into(),
                 format: Format::#format,
+                allowed_in: vec![ #( #allowed_in.into() ),* ],
+                forbidden_in: vec![ #( #forbidden_in.into() ),* ],
+                allowed_children: #allowed_children,
+                one_of: vec![ #( #one_of ),* ],
+                max_per_document: #max_per_document,
+                position: #position,
                 attributes: vec![ #( #attributes ),* ]
             }
         }
@@ -184,46 +389,245 @@ fn implement_spec_list(templates: &[SpecTemplate]) -> TokenStream {
     }
 }
 
+/// Parsing of a repeated attribute to a vector of values, checking the number of values.
+fn implement_repeated_parsing(template: &SpecTemplate, attribute: &SpecAttribute) -> TokenStream {
+    let attr_name = Ident::new(&attribute.identifier, Span::call_site());
+    let template_id = LitStr::new(&template.identifier, Span::call_site());
+    let identifier = LitStr::new(&attribute.identifier, Span::call_site());
+    let find = find_attribute(attribute);
+    let value = attribute_conversion(template, attribute)
+        .unwrap_or_else(|| quote! { arg.value.as_slice() });
+    let min = attribute.min_count();
+    let max = option_to_tokens(attribute.max_count);
+    quote! {
+        #attr_name: {
+            let args = #find;
+            let max: Option<usize> = #max;
+            if args.len() < #min || max.map(|max| args.len() > max).unwrap_or(false) {
+                return Err(ParseError::WrongCount {
+                    template: #template_id.into(),
+                    attribute: #identifier.into(),
+                    min: #min,
+                    max,
+                    found: args.len(),
+                    position: template.position.clone(),
+                })
+            }
+            let mut values = vec![];
+            for arg in args {
+                values.push(#value);
+            }
+            values
+        }
+    }
+}
+
+/// Names and position (as string) an attribute can be given by.
+fn attribute_bindings(attribute: &SpecAttribute) -> Vec<LitStr> {
+    let mut names = str_to_lower_lit(&attribute.names);
+    if let Some(position) = attribute.position {
+        names.push(LitStr::new(&position.to_string(), Span::call_site()));
+    }
+    names
+}
+
+/// Checks for duplicate arguments and predicates of an attribute.
+fn implement_strict_checks(template: &SpecTemplate, attribute: &SpecAttribute) -> TokenStream {
+    let template_id = LitStr::new(&template.identifier, Span::call_site());
+    let identifier = LitStr::new(&attribute.identifier, Span::call_site());
+    let predicate = predicate_expr(template, attribute);
+    let pred_name = LitStr::new(attribute.predicate.trim(), Span::call_site());
+    let check_predicate = quote! {
+        if let Err(error) = (#predicate)(arg.value.as_slice()) {
+            return Err(ParseError::PredicateFailed {
+                template: #template_id.into(),
+                attribute: #identifier.into(),
+                predicate: #pred_name.into(),
+                cause: error.cause,
+                position: template.position.clone(),
+            })
+        }
+    };
+    if attribute.repeated {
+        let find = find_attribute(attribute);
+        return quote! {
+            for arg in #find {
+                #check_predicate
+            }
+        };
+    }
+    let names = accepted_names(attribute);
+    let position = option_to_tokens(attribute.position);
+    quote! {
+        let args = find_all_arguments(&[ #( #names.into() ),* ], #position);
+        if args.len() > 1 {
+            return Err(ParseError::DuplicateArgument {
+                template: #template_id.into(),
+                attribute: #identifier.into(),
+                names: args.iter().map(|arg| arg.name.trim().to_string()).collect(),
+                position: template.position.clone(),
+            })
+        }
+        for arg in args {
+            #check_predicate
+        }
+    }
+}
+
+/// Checks of `requires`, `conflicts_with` and `one_of` against the present attributes.
+fn implement_constraints(template: &SpecTemplate) -> TokenStream {
+    let template_id = LitStr::new(&template.identifier, Span::call_site());
+    let mut checks = vec![];
+    for attribute in &template.attributes {
+        let identifier = LitStr::new(&attribute.identifier, Span::call_site());
+        for required in str_to_lit(&attribute.requires) {
+            checks.push(quote! {
+                if given.contains(&#identifier) && !given.contains(&#required) {
+                    return Err(ParseError::MissingRequirement {
+                        template: #template_id.into(),
+                        attribute: #identifier.into(),
+                        requires: #required.into(),
+                        position: template.position.clone(),
+                    })
+                }
+            });
+        }
+        for conflicting in str_to_lit(&attribute.conflicts_with) {
+            checks.push(quote! {
+                if given.contains(&#identifier) && given.contains(&#conflicting) {
+                    return Err(ParseError::Conflict {
+                        template: #template_id.into(),
+                        attribute: #identifier.into(),
+                        conflicts_with: #conflicting.into(),
+                        position: template.position.clone(),
+                    })
+                }
+            });
+        }
+    }
+    for group in &template.one_of {
+        let group = str_to_lit(group);
+        checks.push(quote! {
+            let group: Vec<String> = vec![ #( #group.into() ),* ];
+            let found: Vec<String> = group
+                .iter()
+                .filter(|id| given.contains(&id.as_str()))
+                .cloned()
+                .collect();
+            if found.len() != 1 {
+                return Err(ParseError::OneOf {
+                    template: #template_id.into(),
+                    attributes: group,
+                    found,
+                    position: template.position.clone(),
+                })
+            }
+        });
+    }
+    if checks.is_empty() {
+        return quote! {};
+    }
+    quote! {
+        let given: Vec<&str> = parsed.present.iter().map(|a| a.name.as_str()).collect();
+        #( { #checks } )*
+    }
+}
+
 fn implement_parsing_match(template: &SpecTemplate) -> TokenStream {
-    let (name, names, format, description) = check_template(template);
+    let (name, names, format, description) = template_idents(template);
     let ident_str = LitStr::new(&template.identifier, Span::call_site());
     let attributes = template.attributes.iter().map(|attr| {
         let attr_name = Ident::new(&attr.identifier, Span::call_site());
-        let alt_names = str_to_lower_lit(&attr.names);
+        if attr.repeated {
+            return implement_repeated_parsing(template, attr);
+        }
+        let find = find_attribute(attr);
+        let value =
+            attribute_conversion(template, attr).unwrap_or_else(|| quote! { arg.value.as_slice() });
         match attr.priority {
-            SpecPriority::Required => quote! {
-                #attr_name: {
-                    // abort template parsing if required argument is missing.
-                    if let Some(c) = extract_content(&[ #( #alt_names.into() ),* ]) {
-                        c
-                    } else {
-                        return None
+            SpecPriority::Required => {
+                let identifier = LitStr::new(&attr.identifier, Span::call_site());
+                let bindings = attribute_bindings(attr);
+                quote! {
+                    #attr_name: match #find {
+                        Some(arg) => #value,
+                        // abort template parsing if required argument is missing.
+                        None => return Err(ParseError::MissingAttribute {
+                            template: #ident_str.into(),
+                            attribute: #identifier.into(),
+                            names: vec![ #( #bindings.into() ),* ],
+                            position: template.position.clone(),
+                        }),
                     }
                 }
-            },
-            SpecPriority::Optional => quote! {
-                #attr_name: extract_content(&[ #( #alt_names.into() ),* ])
+            }
+            SpecPriority::Optional => match attribute_default(attr) {
+                Some(default) => quote! {
+                    #attr_name: match #find {
+                        Some(arg) => #value,
+                        None => #default,
+                    }
+                },
+                None => quote! {
+                    #attr_name: match #find {
+                        Some(arg) => Some(#value),
+                        None => None,
+                    }
+                },
             },
         }
     });
+    let strict_checks = template
+        .attributes
+        .iter()
+        .map(|attr| implement_strict_checks(template, attr));
     let present = template.attributes.iter().map(|attr| {
-        let alt_names = str_to_lower_lit(&attr.names);
+        let find = find_attribute(attr);
         let att_name = LitStr::new(&attr.identifier, Span::call_site());
         let priority = priority_to_ident(attr.priority);
-        quote! {
-            if let Some(value) = extract_content(&[ #( #alt_names.into() ),* ]) {
-                present.push(Attribute {
-                    name: #att_name.into(),
-                    priority: Priority::#priority,
-                    value,
-                });
+        let push = quote! {
+            present.push(Attribute {
+                name: #att_name.into(),
+                priority: Priority::#priority,
+                value: arg.value.as_slice(),
+            });
+        };
+        if attr.repeated {
+            quote! {
+                for arg in #find {
+                    #push
+                }
+            }
+        } else {
+            quote! {
+                if let Some(arg) = #find {
+                    #push
+                }
             }
         }
     });
+    let mut known = vec![];
+    let mut prefixes = vec![];
+    let mut suggested = vec![];
+    for attr in &template.attributes {
+        if attr.repeated {
+            prefixes.append(&mut accepted_names(attr));
+        } else {
+            known.append(&mut attribute_bindings(attr));
+            known.append(&mut str_to_lower_lit(&attr.deprecated_names));
+            suggested.append(&mut str_to_lower_lit(&attr.names));
+        }
+    }
+    let deprecated_names = str_to_lower_lit(&template.deprecated_names);
+    let constraints = implement_constraints(template);
     quote! {
         let names = vec![#( #names.trim().to_lowercase() ),*];
-        if names.contains(&name) {
-            let template = #name {
+        let deprecated_names = [#( #deprecated_names ),*];
+        if names.contains(&name) || deprecated_names.contains(&name.as_str()) {
+            if strict {
+                #( { #strict_checks } )*
+            }
+            let parsed = #name {
                 identifier: #ident_str.into(),
                 names: names,
                 description: #description.into(),
@@ -233,31 +637,365 @@ fn implement_parsing_match(template: &SpecTemplate) -> TokenStream {
                     let mut present = vec![];
                     #( #present )*
                     present
+                },
+                unknown: {
+                    let names: Vec<String> = vec![ #( #known.into() ),* ];
+                    let prefixes: Vec<String> = vec![ #( #prefixes.into() ),* ];
+                    let suggested: Vec<String> = vec![ #( #suggested.into() ),* ];
+                    let mut unknown = vec![];
+                    for (name, arg) in unknown_args(&template.content, &names, &prefixes) {
+                        if let Element::TemplateArgument(ref arg) = *arg {
+                            unknown.push(UnknownArgument {
+                                suggestion: suggest_name(&name, &suggested, &prefixes),
+                                name,
+                                value: &arg.value,
+                                position: arg.position.clone(),
+                            });
+                        }
+                    }
+                    unknown
                 }
             };
-            return Some(KnownTemplate::#name(template));
+            #constraints
+            return Ok(KnownTemplate::#name(parsed));
         }
     }
 }
 
 fn implement_template_parsing(templates: &[SpecTemplate]) -> TokenStream {
-    let template_kinds = templates.iter().map(|t| implement_parsing_match(t));
+    let template_kinds = templates.iter().map(implement_parsing_match);
 
     quote! {
         /// Try to create a `KnownTemplate` variant from an element, using the specification.
+        ///
+        /// Returns `None` if the template is unknown or does not fit the specification,
+        /// `try_parse_template` tells why.
         pub fn parse_template<'e>(template: &'e Template) -> Option<KnownTemplate<'e>> {
-            let extract_content = | attr_names: &[String] | {
-                if let Some(arg) = find_arg(&template.content, attr_names) {
-                    if let Element::TemplateArgument(ref arg) = *arg {
-                        return Some(arg.value.as_slice())
-                    }
+            parse_template_impl(template, false).ok()
+        }
+
+        /// Like `parse_template`, but reports why a template does not fit the specification.
+        ///
+        /// In addition, arguments given multiple times and arguments not satisfying
+        /// the predicate of their attribute are rejected.
+        pub fn try_parse_template<'e>(template: &'e Template) -> Result<KnownTemplate<'e>, ParseError> {
+            parse_template_impl(template, true)
+        }
+
+        /// Parses a template, `strict` enables the checks of `try_parse_template`.
+        fn parse_template_impl<'e>(
+            template: &'e Template,
+            strict: bool,
+        ) -> Result<KnownTemplate<'e>, ParseError> {
+            let find_argument = | attr_names: &[String], position: Option<usize> | {
+                let arg = find_positional_arg(&template.content, attr_names, position);
+                if let Some(Element::TemplateArgument(arg)) = arg {
+                    return Some(arg)
                 }
                 None
             };
+            let find_repeated_arguments = | prefixes: &[String] | {
+                let mut result = vec![];
+                for arg in find_numbered_args(&template.content, prefixes) {
+                    if let Element::TemplateArgument(arg) = arg {
+                        result.push(arg)
+                    }
+                }
+                result
+            };
+
+            let find_all_arguments = | attr_names: &[String], position: Option<usize> | {
+                let mut result = vec![];
+                for arg in find_all_args(&template.content, attr_names, position) {
+                    if let Element::TemplateArgument(arg) = arg {
+                        result.push(arg)
+                    }
+                }
+                result
+            };
 
             let name = extract_plain_text(&template.name).trim().to_lowercase();
             #( #template_kinds )*
-            None
+            Err(ParseError::UnknownTemplate {
+                name,
+                position: template.position.clone(),
+            })
+        }
+    }
+}
+
+/// Template argument content for a reference `value` to an attribute value.
+fn attribute_content(attribute: &SpecAttribute, value: TokenStream) -> TokenStream {
+    match attribute.value_type {
+        SpecType::Elements => quote! { #value.to_vec() },
+        SpecType::Integer | SpecType::Boolean => quote! { text_content(&#value.to_string()) },
+        SpecType::Formula => quote! { formula_content(#value) },
+        SpecType::Text | SpecType::Enum | SpecType::File => quote! { text_content(#value) },
+    }
+}
+
+/// An expression for the default of an attribute, comparable to its value.
+fn default_comparison(attribute: &SpecAttribute) -> Option<TokenStream> {
+    let value = attribute.default.as_ref()?.trim();
+    Some(match attribute.value_type {
+        SpecType::Enum => {
+            let value = value.to_lowercase();
+            quote! { #value }
+        }
+        SpecType::Text | SpecType::Formula | SpecType::File => quote! { #value },
+        _ => attribute_default(attribute)?,
+    })
+}
+
+/// Conversion of a template struct back to a template element.
+fn implement_template_building(template: &SpecTemplate) -> TokenStream {
+    let (name, names, _, _) = template_idents(template);
+    let default_name = &names[0];
+    let arguments = template.attributes.iter().map(|attr| {
+        let field = Ident::new(&attr.identifier, Span::call_site());
+        let attr_name = &attribute_names(attr)[0];
+        let identifier = LitStr::new(&attr.identifier, Span::call_site());
+        let mut content = attribute_content(attr, quote! { value });
+        let mut originals = quote! {};
+        // keep the original content (and markup) of unchanged values.
+        if let Some(parser) = attribute_parser(attr, quote! { original }) {
+            content = quote! {
+                match originals.get(index) {
+                    Some(original) if #parser.as_ref() == Ok(value) => original.to_vec(),
+                    _ => #content,
+                }
+            };
+            originals = quote! {
+                let originals: Vec<&[Element]> = self
+                    .present
+                    .iter()
+                    .filter(|attribute| attribute.name == #identifier)
+                    .map(|attribute| attribute.value)
+                    .collect();
+            };
+            if !attr.repeated {
+                originals = quote! { #originals let index = 0; };
+            }
+        }
+        if attr.repeated {
+            return quote! {
+                #originals
+                for (index, value) in self.#field.iter().enumerate() {
+                    arguments.push((format!("{}{}", #attr_name, index + 1), #content));
+                }
+            };
+        }
+        match (attr.priority, default_comparison(attr)) {
+            (SpecPriority::Optional, None) => quote! {
+                #originals
+                if let Some(ref value) = self.#field {
+                    arguments.push((#attr_name.into(), #content));
+                }
+            },
+            (_, Some(default)) => quote! {
+                #originals
+                let value = &self.#field;
+                if *value != #default {
+                    arguments.push((#attr_name.into(), #content));
+                }
+            },
+            (SpecPriority::Required, None) => quote! {
+                #originals
+                let value = &self.#field;
+                arguments.push((#attr_name.into(), #content));
+            },
+        }
+    });
+    quote! {
+        impl<'e> #name<'e> {
+            /// Rebuilds a template element, using the default names of the template and
+            /// its attributes in the order of the specification. Attributes with their
+            /// default value are left out, unknown arguments are appended. Values which
+            /// were not changed after parsing keep their original content.
+            pub fn to_template(&self) -> Element {
+                let mut arguments: Vec<(String, Vec<Element>)> = vec![];
+                #( { #arguments } )*
+                for argument in &self.unknown {
+                    arguments.push((argument.name.clone(), argument.value.to_vec()));
+                }
+                build_template(#default_name, arguments)
+            }
+
+            /// Serializes this template to wikitext, see `to_template`.
+            pub fn to_wikitext(&self) -> String {
+                to_wikitext(std::slice::from_ref(&self.to_template()))
+            }
+        }
+    }
+}
+
+/// Parameter type and template argument content of a builder setter.
+fn builder_parameter(attribute: &SpecAttribute) -> (TokenStream, TokenStream) {
+    match attribute.value_type {
+        SpecType::Elements => (quote! { Vec<Element> }, quote! { value }),
+        SpecType::Text | SpecType::Enum | SpecType::File => {
+            (quote! { &str }, quote! { text_content(value) })
+        }
+        SpecType::Formula => (quote! { &str }, quote! { formula_content(value) }),
+        SpecType::Integer => (quote! { i64 }, quote! { text_content(&value.to_string()) }),
+        SpecType::Boolean => (quote! { bool }, quote! { text_content(&value.to_string()) }),
+    }
+}
+
+/// A builder for new templates, checked like parsed ones.
+fn implement_builder(template: &SpecTemplate) -> TokenStream {
+    let (name, names, _, _) = template_idents(template);
+    let default_name = &names[0];
+    let builder = Ident::new(
+        &format!("{}Builder", template.identifier),
+        Span::call_site(),
+    );
+    let builder_doc = format!("Builds new `{}` templates, see `{}::builder`.", name, name);
+    let attribute_count = template.attributes.len();
+
+    let template_id = LitStr::new(&template.identifier, Span::call_site());
+    let setters = template.attributes.iter().enumerate().map(|(index, attr)| {
+        let setter = Ident::new(&attr.identifier, Span::call_site());
+        let (parameter, content) = builder_parameter(attr);
+        // text is checked like it would be read from wikitext.
+        let parser = match attr.value_type {
+            SpecType::Text | SpecType::Enum | SpecType::File => {
+                attribute_parser(attr, quote! { &text_value_content(value) })
+            }
+            _ => None,
+        };
+        let check = parser.map(|parser| {
+            let identifier = LitStr::new(&attr.identifier, Span::call_site());
+            let value_type = type_to_ident(attr.value_type);
+            // the first invalid value of a repeated attribute stays invalid.
+            let keep = if attr.repeated {
+                quote! { self.invalid[#index].is_some() }
+            } else {
+                quote! { false }
+            };
+            quote! {
+                if !#keep {
+                    self.invalid[#index] = #parser.err().map(|cause| ConversionError {
+                        attribute: #identifier.into(),
+                        expected: AttributeType::#value_type,
+                        position: mediawiki_parser::Span::any(),
+                        cause,
+                    });
+                }
+            }
+        });
+        let description = attr
+            .description
+            .split('\n')
+            .map(|l| LitStr::new(l, Span::call_site()));
+        let (doc, assignment) = if attr.repeated {
+            (
+                "Adds a value to this attribute:",
+                quote! { self.values[#index].push(#content) },
+            )
+        } else {
+            (
+                "Sets this attribute:",
+                quote! { self.values[#index] = vec![#content] },
+            )
+        };
+        quote! {
+            #[doc = #doc]
+            #( #[doc = #description] )*
+            pub fn #setter(mut self, value: #parameter) -> Self {
+                #check
+                #assignment;
+                self.template = std::sync::OnceLock::new();
+                self
+            }
+        }
+    });
+
+    let arguments = template.attributes.iter().enumerate().map(|(index, attr)| {
+        let attr_name = &attribute_names(attr)[0];
+        if attr.repeated {
+            quote! {
+                for (number, value) in values[#index].iter().enumerate() {
+                    arguments.push((format!("{}{}", #attr_name, number + 1), value.clone()));
+                }
+            }
+        } else {
+            quote! {
+                for value in &values[#index] {
+                    arguments.push((#attr_name.into(), value.clone()));
+                }
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #builder_doc]
+        #[derive(Debug, Clone)]
+        pub struct #builder {
+            /// Argument contents, by attribute in the order of the specification.
+            values: Vec<Vec<Vec<Element>>>,
+            /// Values which are not valid for their attribute, in the same order.
+            invalid: Vec<Option<ConversionError>>,
+            /// The template element, built by `build`.
+            template: std::sync::OnceLock<Element>,
+        }
+
+        impl PartialEq for #builder {
+            fn eq(&self, other: &Self) -> bool {
+                self.values == other.values && self.invalid == other.invalid
+            }
+        }
+
+        impl<'e> #name<'e> {
+            /// A builder for a new template, using the default names of the template
+            /// and its attributes. Text values are kept as given and escaped by
+            /// `to_wikitext`, but may not contain markup like links or templates.
+            pub fn builder() -> #builder {
+                #builder {
+                    values: vec![vec![]; #attribute_count],
+                    invalid: vec![None; #attribute_count],
+                    template: std::sync::OnceLock::new(),
+                }
+            }
+
+            fn builder_template(values: &[Vec<Vec<Element>>]) -> Element {
+                let mut arguments: Vec<(String, Vec<Element>)> = vec![];
+                #( #arguments )*
+                build_template(#default_name, arguments)
+            }
+        }
+
+        impl #builder {
+            #( #setters )*
+
+            /// The template built so far, checked like `try_parse_template` does.
+            /// It borrows its content from the builder.
+            pub fn build(&self) -> Result<#name<'_>, ParseError> {
+                if let Some(error) = self.invalid.iter().flatten().next() {
+                    return Err(ParseError::Conversion {
+                        template: #template_id.into(),
+                        error: error.clone(),
+                        position: mediawiki_parser::Span::any(),
+                    });
+                }
+                let template = self
+                    .template
+                    .get_or_init(|| #name::builder_template(&self.values));
+                let template = match *template {
+                    Element::Template(ref template) => template,
+                    _ => unreachable!("builders build templates!"),
+                };
+                match try_parse_template(template)? {
+                    KnownTemplate::#name(template) => Ok(template),
+                    _ => unreachable!("builders build their own kind of template!"),
+                }
+            }
+
+            /// The template element built so far, checked like `build` does.
+            pub fn into_template(self) -> Result<Element, ParseError> {
+                self.build()?;
+                Ok(self.template.into_inner().expect("built by `build`!"))
+            }
         }
     }
 }
@@ -266,25 +1004,38 @@ fn implement_templates(templates: &[SpecTemplate]) -> Vec<TokenStream> {
     templates
         .iter()
         .map(|template| {
-            let (name, names, _, _) = check_template(template);
+            let (name, names, _, _) = template_idents(template);
+            let building = implement_template_building(template);
+            let builder = implement_builder(template);
             let description = template
                 .description
                 .split('\n')
-                .map(|l| LitStr::new(&l, Span::call_site()));
+                .map(|l| LitStr::new(l, Span::call_site()));
             let attribute_impls = template.attributes.iter().map(|attr| {
                 let attr_id: Ident = Ident::new(&attr.identifier, Span::call_site());
                 let description = attr
                     .description
                     .split('\n')
-                    .map(|l| LitStr::new(&l, Span::call_site()));
+                    .map(|l| LitStr::new(l, Span::call_site()));
+                let field_type = attribute_field_type(attr);
+                if attr.repeated {
+                    return quote! {
+                        #( #[doc = #description] )*
+                        pub #attr_id: Vec<#field_type>
+                    };
+                }
                 match attr.priority {
                     SpecPriority::Required => quote! {
                         #( #[doc = #description] )*
-                        pub #attr_id: &'e [Element]
+                        pub #attr_id: #field_type
+                    },
+                    SpecPriority::Optional if attr.default.is_some() => quote! {
+                        #( #[doc = #description] )*
+                        pub #attr_id: #field_type
                     },
                     SpecPriority::Optional => quote! {
                         #( #[doc = #description] )*
-                        pub #attr_id: Option<&'e [Element]>
+                        pub #attr_id: Option<#field_type>
                     },
                 }
             });
@@ -301,42 +1052,1242 @@ fn implement_templates(templates: &[SpecTemplate]) -> Vec<TokenStream> {
                     pub format: Format,
                     pub description: String,
                     pub present: Vec<Attribute<'e>>,
+                    /// Arguments which do not belong to any attribute.
+                    pub unknown: Vec<UnknownArgument<'e>>,
                     # (#attribute_impls ),*
                 }
+
+                #building
+
+                #builder
             }
         })
         .collect()
 }
 
-fn read_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
-    let mut file = fs::File::open(path.as_ref())?;
-    let mut string = String::new();
-    file.read_to_string(&mut string)?;
-    Ok(string)
+/// Export of the specification to TemplateData, part of `spec_meta`.
+fn implement_template_data() -> TokenStream {
+    quote! {
+        impl AttributeType {
+            /// The closest TemplateData parameter type.
+            pub fn template_data_type(self) -> &'static str {
+                match self {
+                    AttributeType::Elements => "content",
+                    AttributeType::Text => "line",
+                    AttributeType::Integer => "number",
+                    AttributeType::Boolean => "boolean",
+                    AttributeType::Enum | AttributeType::Formula => "string",
+                    AttributeType::File => "wiki-file-name",
+                }
+            }
+        }
+
+        impl<'p> TemplateSpec<'p> {
+            /// Describes this template as TemplateData for VisualEditor.
+            ///
+            /// Parameters are named by the first name of their attribute, or its position
+            /// if it has no names. Other names, the position and deprecated names are aliases.
+            /// Repeated attributes become numbered parameters, up to `max_count` or at least one.
+            pub fn template_data(&self) -> ::mwparser_utils::template_data::TemplateData {
+                let mut params = std::collections::BTreeMap::new();
+                let mut param_order = vec![];
+                for attribute in &self.attributes {
+                    let mut names = attribute.names.clone();
+                    if let Some(position) = attribute.position {
+                        names.push(position.to_string());
+                    }
+                    names.extend(attribute.deprecated_names.iter().cloned());
+                    let param = ::mwparser_utils::template_data::TemplateDataParam {
+                        label: String::new(),
+                        description: attribute.description.clone(),
+                        param_type: attribute.value_type.template_data_type().into(),
+                        required: attribute.priority == Priority::Required,
+                        aliases: names[1..].to_vec(),
+                        default: attribute.default.clone(),
+                        suggested_values: attribute.values.clone(),
+                    };
+                    if !attribute.repeated {
+                        param_order.push(names[0].clone());
+                        params.insert(names[0].clone(), param);
+                        continue;
+                    }
+                    let count = attribute
+                        .max_count
+                        .unwrap_or_else(|| attribute.min_count.max(1));
+                    for number in 1..=count {
+                        let numbered = |name: &String| format!("{}{}", name, number);
+                        let name = numbered(&names[0]);
+                        param_order.push(name.clone());
+                        params.insert(
+                            name,
+                            ::mwparser_utils::template_data::TemplateDataParam {
+                                required: number <= attribute.min_count,
+                                aliases: names[1..].iter().map(numbered).collect(),
+                                ..param.clone()
+                            },
+                        );
+                    }
+                }
+                ::mwparser_utils::template_data::TemplateData {
+                    description: self.description.clone(),
+                    params,
+                    param_order,
+                    format: match self.format {
+                        Format::Inline => "inline",
+                        Format::Block | Format::Box => "block",
+                    }
+                    .into(),
+                }
+            }
+        }
+    }
 }
 
-#[proc_macro]
-pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let path_lit: LitStr = syn::parse(input.into()).expect("could not parse path string!");
-    let path = path_lit.value();
+/// Predicate types and checks, part of `spec_meta`.
+fn implement_predicates() -> TokenStream {
+    quote! {
+        /// Represents failure of a predicate check.
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct PredError<'e> {
+            pub tree: Option<&'e Element>,
+            pub cause: String,
+        }
 
-    let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
-    let path = Path::new(&root).join(&path);
-    let file_name = match path.file_name() {
-        Some(file_name) => file_name,
-        None => panic!("spec attribute should point to a file"),
-    };
+        /// Result of a predicate check.
+        pub type PredResult<'e> = Result<(), PredError<'e>>;
+        /// A function to determine wether a given element is allowed.
+        pub type Predicate = Fn(&[Element]) -> PredResult + Sync;
 
-    let data = match read_file(&path) {
-        Ok(data) => data,
-        Err(error) => panic!("error opening {:?}: {}", file_name, error),
-    };
-    let templates: Vec<SpecTemplate> = serde_yaml::from_str(&data).expect("cannot parse spec:");
+        /// A predicate failure found by `always_collect`, with its location.
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct PredFailure<'e> {
+            pub error: PredError<'e>,
+            /// Ancestors of the failing elements, starting at the checked content.
+            pub path: Vec<&'e Element>,
+            /// Position of `error.tree`, or of the failing elements if it is `None`.
+            pub position: Span,
+        }
 
-    let template_id = implement_template_id(&templates);
-    let template_impls = implement_templates(&templates);
-    let spec_func = implement_spec_list(&templates);
+        /// Checks a predicate for a given input tree.
+        struct TreeChecker<'path, 'e> {
+            pub path: Vec<&'path Element>,
+            pub result: PredResult<'e>,
+            /// Continue after a failure, collecting all of them in `failures`.
+            pub accumulate: bool,
+            pub failures: Vec<PredFailure<'e>>,
+        }
+
+        impl<'e> TreeChecker<'e, 'e> {
+            fn new(accumulate: bool) -> Self {
+                TreeChecker {
+                    path: vec![],
+                    result: Ok(()),
+                    accumulate,
+                    failures: vec![],
+                }
+            }
+
+            /// Position of a failure in `root`.
+            fn failure_position(&self, root: &'e [Element], error: &PredError<'e>) -> Span {
+                if let Some(tree) = error.tree {
+                    return tree.get_position().clone();
+                }
+                match (root.first(), root.last(), self.path.last()) {
+                    (Some(first), Some(last), _) => Span {
+                        start: first.get_position().start.clone(),
+                        end: last.get_position().end.clone(),
+                    },
+                    (_, _, Some(parent)) => parent.get_position().clone(),
+                    _ => Span::any(),
+                }
+            }
+        }
+
+        impl <'e, 'p: 'e> Traversion<'e, &'p Predicate> for TreeChecker<'e, 'e> {
+
+            fn path_push(&mut self, root: &'e Element) {
+                self.path.push(root);
+            }
+            fn path_pop(&mut self) -> Option<&'e Element> {
+                self.path.pop()
+            }
+            fn get_path(&self) -> &Vec<&'e Element> {
+                &self.path
+            }
+
+            fn work_vec(
+                &mut self,
+                root: &'e [Element],
+                predicate: &'p Predicate,
+                _: &mut io::Write
+            ) -> io::Result<bool> {
+                if self.result.is_err() {
+                    return Ok(false)
+                }
+                if let Err(error) = (predicate)(root) {
+                    if !self.accumulate {
+                        self.result = Err(error);
+                        return Ok(false)
+                    }
+                    let failure = PredFailure {
+                        position: self.failure_position(root, &error),
+                        path: self.get_path().clone(),
+                        error,
+                    };
+                    // checks of enclosing content may already have found this one.
+                    if !self.failures.iter().any(|f| {
+                        f.position == failure.position && f.error.cause == failure.error.cause
+                    }) {
+                        self.failures.push(failure);
+                    }
+                }
+                Ok(true)
+            }
+        }
+
+        /// Checks a predicate recursively.
+        pub fn always<'e, 'p: 'e>(root: &'e [Element], predicate: &'p Predicate)
+            -> PredResult<'e>
+        {
+            let mut checker = TreeChecker::new(false);
+            checker.run_vec(&root, predicate, &mut vec![])
+                .expect("error checking predicate!");
+            checker.result
+        }
+
+        /// Checks a predicate recursively like `always`, but returns all failures
+        /// instead of stopping at the first one.
+        pub fn always_collect<'e, 'p: 'e>(root: &'e [Element], predicate: &'p Predicate)
+            -> Vec<PredFailure<'e>>
+        {
+            let mut checker = TreeChecker::new(true);
+            checker.run_vec(&root, predicate, &mut vec![])
+                .expect("error checking predicate!");
+            checker.failures
+        }
+    }
+}
+
+/// Common predicates and combinators, part of `spec_meta`.
+///
+/// Predicates check the elements of the content they are given, not their children.
+/// Use `always` to check the whole tree.
+fn implement_predicate_library() -> TokenStream {
+    quote! {
+        fn pred_error<'e>(tree: &'e Element, cause: String) -> PredResult<'e> {
+            Err(PredError {
+                tree: Some(tree),
+                cause,
+            })
+        }
+
+        /// Allows only text and comments.
+        pub fn only_text(root: &[Element]) -> PredResult {
+            for element in root {
+                match *element {
+                    Element::Text(_) | Element::Comment(_) => (),
+                    _ => return pred_error(
+                        element,
+                        format!("only text is allowed here, found {}!", element.get_variant_name()),
+                    ),
+                }
+            }
+            Ok(())
+        }
+
+        /// Forbids templates.
+        pub fn no_templates(root: &[Element]) -> PredResult {
+            for element in root {
+                if let Element::Template(_) = *element {
+                    return pred_error(element, "templates are not allowed here!".into());
+                }
+            }
+            Ok(())
+        }
+
+        /// Forbids paragraphs, headings, lists, tables and galleries.
+        pub fn no_block_elements(root: &[Element]) -> PredResult {
+            for element in root {
+                match *element {
+                    Element::Paragraph(_)
+                    | Element::Heading(_)
+                    | Element::List(_)
+                    | Element::ListItem(_)
+                    | Element::Table(_)
+                    | Element::TableRow(_)
+                    | Element::TableCell(_)
+                    | Element::Gallery(_) => {
+                        return pred_error(
+                            element,
+                            format!(
+                                "block elements are not allowed here, found {}!",
+                                element.get_variant_name()
+                            ),
+                        )
+                    }
+                    _ => (),
+                }
+            }
+            Ok(())
+        }
+
+        /// Forbids headings.
+        pub fn no_headings(root: &[Element]) -> PredResult {
+            for element in root {
+                if let Element::Heading(_) = *element {
+                    return pred_error(element, "headings are not allowed here!".into());
+                }
+            }
+            Ok(())
+        }
+
+        /// Limits the length of the plain text (see `extract_plain_text`) in characters.
+        pub fn max_length(root: &[Element], max: usize) -> PredResult {
+            let length = super::extract_plain_text(root).chars().count();
+            if length > max {
+                return Err(PredError {
+                    tree: root.first(),
+                    cause: format!("text is {} characters long, at most {} are allowed!", length, max),
+                });
+            }
+            Ok(())
+        }
+
+        /// Requires the plain text (see `extract_plain_text`) to match a regular expression.
+        pub fn matches<'e>(root: &'e [Element], pattern: &str) -> PredResult<'e> {
+            let text = super::extract_plain_text(root);
+            match super::matches_pattern(text.trim(), pattern) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(PredError {
+                    tree: root.first(),
+                    cause: format!("{:?} does not match {:?}!", text.trim(), pattern),
+                }),
+                Err(cause) => Err(PredError {
+                    tree: root.first(),
+                    cause,
+                }),
+            }
+        }
+
+        /// Allows only elements of the given kinds, like `Text` or `Formatted`
+        /// (see `Element::get_variant_name`).
+        pub fn only_kinds<'e>(root: &'e [Element], kinds: &[&str]) -> PredResult<'e> {
+            for element in root {
+                let kind = element.get_variant_name();
+                if !kinds.iter().any(|k| k.eq_ignore_ascii_case(kind)) {
+                    return pred_error(
+                        element,
+                        format!("{} is not allowed here, only {}!", kind, kinds.join(", ")),
+                    );
+                }
+            }
+            Ok(())
+        }
+
+        /// Requires all predicates to hold, returning the first failure.
+        pub fn all_of<'e>(root: &'e [Element], predicates: &[&Predicate]) -> PredResult<'e> {
+            for predicate in predicates {
+                predicate(root)?;
+            }
+            Ok(())
+        }
+
+        /// Requires one of the predicates to hold.
+        pub fn any_of<'e>(root: &'e [Element], predicates: &[&Predicate]) -> PredResult<'e> {
+            let mut causes = vec![];
+            for predicate in predicates {
+                match predicate(root) {
+                    Ok(()) => return Ok(()),
+                    Err(error) => causes.push(error.cause),
+                }
+            }
+            Err(PredError {
+                tree: root.first(),
+                cause: format!("none of the alternatives hold: {}", causes.join(" / ")),
+            })
+        }
+
+        /// Requires a predicate to fail, `cause` describes the failure of this check.
+        pub fn not<'e>(root: &'e [Element], predicate: &Predicate, cause: &str) -> PredResult<'e> {
+            match predicate(root) {
+                Ok(()) => Err(PredError {
+                    tree: root.first(),
+                    cause: cause.into(),
+                }),
+                Err(_) => Ok(()),
+            }
+        }
+    }
+}
+
+/// Validation of whole documents, part of `spec_meta`.
+fn implement_validation() -> TokenStream {
+    quote! {
+        /// Severity of a `Diagnostic`.
+        #[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+        pub enum Severity {
+            Error,
+            Warning,
+        }
+
+        /// A problem found when validating a document against the specification.
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        pub struct Diagnostic {
+            pub severity: Severity,
+            pub message: String,
+            pub position: Span,
+        }
+
+        impl Diagnostic {
+            fn error(message: String, position: &Span) -> Diagnostic {
+                Diagnostic {
+                    severity: Severity::Error,
+                    message,
+                    position: position.clone(),
+                }
+            }
+        }
+
+        impl<'p> AttributeSpec<'p> {
+            /// All names this attribute is recognized by, including deprecated ones.
+            fn accepted_names(&self) -> Vec<String> {
+                self.names
+                    .iter()
+                    .chain(&self.deprecated_names)
+                    .map(|name| name.trim().to_lowercase())
+                    .collect()
+            }
+        }
+
+        impl<'p> TemplateSpec<'p> {
+            /// The arguments of a template bound to an attribute, like the `present()`
+            /// attributes of a parsed template. Works for templates which fail to parse.
+            pub fn attribute_arguments<'e>(
+                &self,
+                template: &'e Template,
+            ) -> Vec<(&AttributeSpec<'p>, &'e TemplateArgument)> {
+                let mut result = vec![];
+                for attribute in &self.attributes {
+                    let names = attribute.accepted_names();
+                    let arguments = if attribute.repeated {
+                        super::find_numbered_args(&template.content, &names)
+                    } else {
+                        super::find_positional_arg(&template.content, &names, attribute.position)
+                            .into_iter()
+                            .collect()
+                    };
+                    for argument in arguments {
+                        if let Element::TemplateArgument(ref argument) = *argument {
+                            result.push((attribute, argument));
+                        }
+                    }
+                }
+                result
+            }
+
+            /// The arguments of a template which belong to no attribute, like `unknown()`
+            /// of a parsed template. Works for templates which fail to parse.
+            pub fn unknown_arguments<'e>(&self, template: &'e Template) -> Vec<UnknownArgument<'e>> {
+                let mut names = vec![];
+                let mut prefixes = vec![];
+                let mut suggested = vec![];
+                for attribute in &self.attributes {
+                    if attribute.repeated {
+                        prefixes.append(&mut attribute.accepted_names());
+                    } else {
+                        names.append(&mut attribute.accepted_names());
+                        names.extend(attribute.position.map(|position| position.to_string()));
+                        suggested.extend(attribute.names.iter().map(|n| n.trim().to_lowercase()));
+                    }
+                }
+                let mut unknown = vec![];
+                for (name, arg) in super::unknown_args(&template.content, &names, &prefixes) {
+                    if let Element::TemplateArgument(ref arg) = *arg {
+                        unknown.push(UnknownArgument {
+                            suggestion: super::suggest_name(&name, &suggested, &prefixes),
+                            name,
+                            value: &arg.value,
+                            position: arg.position.clone(),
+                        });
+                    }
+                }
+                unknown
+            }
+        }
+
+        /// Validates a single template, without templates nested in its arguments.
+        ///
+        /// Templates which do not fit the specification (see `try_parse_template`) and
+        /// all parts of attribute values failing their predicate (see `always_collect`)
+        /// are errors, unknown arguments and deprecated names are warnings. All of them
+        /// are reported, even if the template does not fit the specification.
+        /// Templates missing from the specification are not checked at all.
+        pub fn validate_template(template: &Template) -> Vec<Diagnostic> {
+            // templates missing from the specification are not checked.
+            let spec = match super::spec_of(&super::extract_plain_text(&template.name)) {
+                Some(spec) => spec,
+                None => return vec![],
+            };
+            let parsed = match super::try_parse_template(template) {
+                // failing predicates are reported with their position below.
+                Err(ParseError::PredicateFailed { .. }) => {
+                    super::parse_template_impl(template, false)
+                }
+                parsed => parsed,
+            };
+            let mut diagnostics = vec![];
+            if let Err(ref error) = parsed {
+                diagnostics.push(Diagnostic::error(error.to_string(), error.position()));
+            }
+            for (attribute_spec, argument) in spec.attribute_arguments(template) {
+                for failure in always_collect(&argument.value, attribute_spec.predicate) {
+                    let message = format!(
+                        "{}: `{}` does not satisfy {}: {}",
+                        spec.identifier,
+                        attribute_spec.identifier,
+                        attribute_spec.predicate_name,
+                        failure.error.cause
+                    );
+                    diagnostics.push(Diagnostic::error(message, &failure.position));
+                }
+            }
+            diagnostics.append(&mut deprecation_warnings(&spec, template));
+            for argument in spec.unknown_arguments(template) {
+                let mut message = format!("{}: unknown argument `{}`", spec.identifier, argument.name);
+                if let Some(ref suggestion) = argument.suggestion {
+                    message.push_str(&format!(", did you mean `{}`?", suggestion));
+                }
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    message,
+                    position: argument.position.clone(),
+                });
+            }
+            diagnostics
+        }
+
+        /// Errors for a template placed where the specification does not allow it
+        /// (`allowed_in`, `forbidden_in` and `allowed_children` of the templates involved).
+        /// `path` are the ancestors of the template, only templates of the specification count.
+        pub fn placement_errors(template: &Template, path: &[&Element]) -> Vec<Diagnostic> {
+            let spec = match super::spec_of(&super::extract_plain_text(&template.name)) {
+                Some(spec) => spec,
+                None => return vec![],
+            };
+            let enclosing: Vec<TemplateSpec> = path
+                .iter()
+                .filter_map(|element| match **element {
+                    Element::Template(ref t) => super::spec_of(&super::extract_plain_text(&t.name)),
+                    _ => None,
+                })
+                .collect();
+
+            let mut messages = vec![];
+            if let Some(parent) = enclosing.iter().rev().find(|p| spec.forbidden_in.contains(&p.identifier)) {
+                messages.push(format!("{}: not allowed inside `{}`", spec.identifier, parent.identifier));
+            }
+            if !spec.allowed_in.is_empty()
+                && !enclosing.iter().any(|p| spec.allowed_in.contains(&p.identifier))
+            {
+                let allowed: Vec<String> = spec.allowed_in.iter().map(|id| format!("`{}`", id)).collect();
+                messages.push(format!("{}: only allowed inside {}", spec.identifier, allowed.join(", ")));
+            }
+            if let Some(parent) = enclosing.last() {
+                if let Some(ref children) = parent.allowed_children {
+                    if !children.contains(&spec.identifier) {
+                        messages.push(format!(
+                            "{}: `{}` is not allowed as a child",
+                            parent.identifier, spec.identifier
+                        ));
+                    }
+                }
+            }
+            messages
+                .into_iter()
+                .map(|message| Diagnostic::error(message, &template.position))
+                .collect()
+        }
+
+        /// Errors for a template not fitting its `Format`: block content in the arguments of
+        /// an `Inline` template, `Block` and `Box` templates inside a paragraph or inside the
+        /// arguments of an `Inline` template. `path` are the ancestors of the template.
+        pub fn format_errors(template: &Template, path: &[&Element]) -> Vec<Diagnostic> {
+            let spec = match super::spec_of(&super::extract_plain_text(&template.name)) {
+                Some(spec) => spec,
+                None => return vec![],
+            };
+            let mut diagnostics = vec![];
+            if spec.format == Format::Inline {
+                for argument in &template.content {
+                    if let Element::TemplateArgument(ref argument) = *argument {
+                        // nested templates are checked on their own.
+                        let failures = always_collect(&argument.value, &no_block_elements)
+                            .into_iter()
+                            .filter(|f| !f.path.iter().any(|e| matches!(**e, Element::Template(_))));
+                        for failure in failures {
+                            let message = format!(
+                                "{}: block content in inline template: {}",
+                                spec.identifier, failure.error.cause
+                            );
+                            diagnostics.push(Diagnostic::error(message, &failure.position));
+                        }
+                    }
+                }
+                return diagnostics;
+            }
+
+            let format = if spec.format == Format::Box { "box" } else { "block" };
+            // only whitespace and comments may surround a block template in a paragraph.
+            if let Some(Element::Paragraph(ref paragraph)) = path.last().map(|e| *e) {
+                let surrounded = paragraph.content.iter().any(|element| match *element {
+                    Element::Template(ref other) => !std::ptr::eq(other, template),
+                    Element::Text(ref text) => !text.text.trim().is_empty(),
+                    Element::Comment(_) => false,
+                    _ => true,
+                });
+                if surrounded {
+                    let message = format!("{}: {} template inside a paragraph", spec.identifier, format);
+                    diagnostics.push(Diagnostic::error(message, &template.position));
+                }
+            }
+            let parent = path.iter().rev().filter_map(|element| match **element {
+                Element::Template(ref t) => super::spec_of(&super::extract_plain_text(&t.name)),
+                _ => None,
+            }).next();
+            if let Some(parent) = parent.filter(|p| p.format == Format::Inline) {
+                let message = format!(
+                    "{}: {} template inside inline template `{}`",
+                    spec.identifier, format, parent.identifier
+                );
+                diagnostics.push(Diagnostic::error(message, &template.position));
+            }
+            diagnostics
+        }
+
+        /// Collects the diagnostics of all templates in a document.
+        struct DocumentChecker<'e> {
+            pub path: Vec<&'e Element>,
+            pub diagnostics: Vec<Diagnostic>,
+        }
+
+        impl<'e> Traversion<'e, ()> for DocumentChecker<'e> {
+            super::path_methods!('e);
+
+            fn work(&mut self, root: &'e Element, _: (), _: &mut io::Write) -> io::Result<bool> {
+                if let Element::Template(ref template) = *root {
+                    self.diagnostics.append(&mut validate_template(template));
+                    let ancestors = &self.path[..self.path.len() - 1];
+                    self.diagnostics.append(&mut placement_errors(template, ancestors));
+                    self.diagnostics.append(&mut format_errors(template, ancestors));
+                }
+                Ok(true)
+            }
+        }
+
+        /// Validates all templates of a document, including nested ones,
+        /// their placement (see `placement_errors`), format (see `format_errors`)
+        /// and occurrences (see `validate_occurrences`).
+        pub fn validate_document(root: &Element) -> Vec<Diagnostic> {
+            let mut checker = DocumentChecker {
+                path: vec![],
+                diagnostics: vec![],
+            };
+            checker.run(root, (), &mut vec![]).expect("error validating document!");
+            checker.diagnostics.append(&mut validate_occurrences(root));
+            checker.diagnostics
+        }
+
+        /// Replace templates with validation errors by an `Element::Error`.
+        /// Warnings are ignored, templates not in the specification are kept.
+        pub fn mark_invalid_templates(root: Element, settings: ()) -> TResult {
+            if let Element::Template(ref template) = root {
+                let messages: Vec<String> = validate_template(template)
+                    .into_iter()
+                    .filter(|d| d.severity == Severity::Error)
+                    .map(|d| d.message)
+                    .collect();
+                if !messages.is_empty() {
+                    return Ok(Element::Error(Error {
+                        position: template.position.clone(),
+                        message: messages.join("\n"),
+                    }));
+                }
+            }
+            recurse_inplace(&mark_invalid_templates, root, settings)
+        }
+    }
+}
+
+/// Per-document limits of templates, part of `spec_meta`.
+fn implement_occurrences() -> TokenStream {
+    quote! {
+        /// Wether an element may precede (or follow) a template which has to be at
+        /// `position` of a document: whitespace, comments and templates with the same position.
+        fn ignorable(element: &Element, position: DocumentPosition) -> bool {
+            match *element {
+                Element::Text(ref text) => text.text.trim().is_empty(),
+                Element::Comment(_) => true,
+                Element::Paragraph(ref paragraph) => {
+                    paragraph.content.iter().all(|e| ignorable(e, position))
+                }
+                Element::Template(ref template) => {
+                    super::spec_of(&super::extract_plain_text(&template.name))
+                        .map(|spec| spec.position == Some(position))
+                        .unwrap_or(false)
+                }
+                _ => false,
+            }
+        }
+
+        /// Wether `element` is preceded (`First`) or followed (`Last`) only by ignorable elements.
+        fn at_edge(content: &[Element], element: &Element, position: DocumentPosition) -> bool {
+            let index = match content.iter().position(|e| std::ptr::eq(e, element)) {
+                Some(index) => index,
+                None => return false,
+            };
+            let others = match position {
+                DocumentPosition::First => &content[..index],
+                DocumentPosition::Last => &content[index + 1..],
+            };
+            others.iter().all(|e| ignorable(e, position))
+        }
+
+        /// The content of an element a template at `position` of the document may be in.
+        fn edge_content(element: &Element, position: DocumentPosition) -> Option<&[Element]> {
+            match *element {
+                Element::Document(ref doc) => Some(&doc.content),
+                Element::Paragraph(ref paragraph) => Some(&paragraph.content),
+                // the caption of a heading precedes its content.
+                Element::Heading(ref heading) if position == DocumentPosition::Last => {
+                    Some(&heading.content)
+                }
+                _ => None,
+            }
+        }
+
+        /// Collects the templates of a document with their ancestors.
+        struct OccurrenceChecker<'e> {
+            pub path: Vec<&'e Element>,
+            pub templates: Vec<(&'e Element, Vec<&'e Element>)>,
+        }
+
+        impl<'e> Traversion<'e, ()> for OccurrenceChecker<'e> {
+            super::path_methods!('e);
+
+            fn work(&mut self, root: &'e Element, _: (), _: &mut io::Write) -> io::Result<bool> {
+                if let Element::Template(_) = *root {
+                    let ancestors = self.path[..self.path.len() - 1].to_vec();
+                    self.templates.push((root, ancestors));
+                }
+                Ok(true)
+            }
+        }
+
+        /// Checks the occurrences of templates in a document: templates exceeding
+        /// `max_per_document` and templates not at the `position` of the document
+        /// they belong to are errors. A template at the start or end of a document
+        /// may be on its own in a paragraph, a template at the end also in the content
+        /// of the last heading, but not nested in anything else.
+        pub fn validate_occurrences(root: &Element) -> Vec<Diagnostic> {
+            let mut checker = OccurrenceChecker {
+                path: vec![],
+                templates: vec![],
+            };
+            checker.run(root, (), &mut vec![]).expect("error validating document!");
+
+            let mut diagnostics = vec![];
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for (element, ancestors) in checker.templates {
+                let template = match *element {
+                    Element::Template(ref template) => template,
+                    _ => continue,
+                };
+                let spec = match super::spec_of(&super::extract_plain_text(&template.name)) {
+                    Some(spec) => spec,
+                    None => continue,
+                };
+                let count = counts.entry(spec.identifier.clone()).or_insert(0);
+                *count += 1;
+                if let Some(max) = spec.max_per_document.filter(|max| *count > *max) {
+                    let message = format!("{}: at most {} allowed per document", spec.identifier, max);
+                    diagnostics.push(Diagnostic::error(message, &template.position));
+                }
+
+                let position = match spec.position {
+                    Some(position) => position,
+                    None => continue,
+                };
+                let mut chain = ancestors.clone();
+                chain.push(element);
+                let placed = match ancestors.first() {
+                    Some(&&Element::Document(_)) => chain.windows(2).all(|pair| {
+                        edge_content(pair[0], position)
+                            .map(|content| at_edge(content, pair[1], position))
+                            .unwrap_or(false)
+                    }),
+                    _ => false,
+                };
+                if !placed {
+                    let edge = match position {
+                        DocumentPosition::First => "start",
+                        DocumentPosition::Last => "end",
+                    };
+                    let message = format!("{}: must be at the {} of the document", spec.identifier, edge);
+                    diagnostics.push(Diagnostic::error(message, &template.position));
+                }
+            }
+            diagnostics
+        }
+    }
+}
+
+/// Deprecated names and their migration, part of `spec_meta`.
+fn implement_deprecation() -> TokenStream {
+    quote! {
+        impl<'p> AttributeSpec<'p> {
+            /// The current name for an argument given by a deprecated name of this
+            /// attribute, keeping the number of repeated attributes.
+            pub fn renamed(&self, name: &str) -> Option<String> {
+                let name = name.trim().to_lowercase();
+                for deprecated in &self.deprecated_names {
+                    if self.repeated {
+                        if let Some(number) = super::name_number(&name, deprecated) {
+                            return Some(format!("{}{}", self.default_name(), number));
+                        }
+                    } else if name == *deprecated {
+                        return Some(self.default_name().to_string());
+                    }
+                }
+                None
+            }
+        }
+
+        impl<'p> TemplateSpec<'p> {
+            /// The current name for an argument given by a deprecated attribute name.
+            pub fn renamed_argument(&self, name: &str) -> Option<String> {
+                self.attributes.iter().filter_map(|a| a.renamed(name)).next()
+            }
+        }
+
+        /// Warnings for deprecated template and argument names.
+        fn deprecation_warnings(spec: &TemplateSpec, template: &Template) -> Vec<Diagnostic> {
+            let mut diagnostics = vec![];
+            let name = super::extract_plain_text(&template.name).trim().to_lowercase();
+            if spec.deprecated_names.contains(&name) {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Warning,
+                    message: format!(
+                        "{}: template name `{}` is deprecated, use `{}`",
+                        spec.identifier, name, spec.default_name()
+                    ),
+                    position: template.position.clone(),
+                });
+            }
+            for argument in &template.content {
+                if let Element::TemplateArgument(ref argument) = *argument {
+                    if let Some(renamed) = spec.renamed_argument(&argument.name) {
+                        diagnostics.push(Diagnostic {
+                            severity: Severity::Warning,
+                            message: format!(
+                                "{}: argument name `{}` is deprecated, use `{}`",
+                                spec.identifier, argument.name.trim(), renamed
+                            ),
+                            position: argument.position.clone(),
+                        });
+                    }
+                }
+            }
+            diagnostics
+        }
+
+        /// Replaces deprecated template and argument names by their `default_name()`.
+        pub fn migrate_deprecated_names(mut root: Element, settings: ()) -> TResult {
+            if let Element::Template(ref mut template) = root {
+                let name = super::extract_plain_text(&template.name).trim().to_lowercase();
+                if let Some(spec) = super::spec_of(&name) {
+                    if spec.deprecated_names.contains(&name) {
+                        template.name = super::text_content(spec.default_name());
+                    }
+                    for argument in &mut template.content {
+                        if let Element::TemplateArgument(ref mut argument) = *argument {
+                            if let Some(renamed) = spec.renamed_argument(&argument.name) {
+                                argument.name = renamed;
+                            }
+                        }
+                    }
+                }
+            }
+            recurse_inplace(&migrate_deprecated_names, root, settings)
+        }
+    }
+}
+
+/// Canonical form of template invocations, part of `spec_meta`.
+fn implement_canonicalization() -> TokenStream {
+    quote! {
+        impl<'p> TemplateSpec<'p> {
+            /// The attribute an argument bound to `name` (lowercase) belongs to, with the
+            /// number of a repeated attribute and whether it is bound by its position.
+            fn bind_argument(&self, name: &str) -> Option<(usize, usize, bool)> {
+                for (index, attribute) in self.attributes.iter().enumerate() {
+                    let mut names = attribute.names.iter().chain(&attribute.deprecated_names);
+                    if attribute.repeated {
+                        let number = names.find_map(|prefix| super::name_number(name, prefix));
+                        if let Some(number) = number {
+                            return Some((index, number, false));
+                        }
+                    } else if names.any(|n| n == name) {
+                        return Some((index, 0, false));
+                    } else if attribute.position.map(|p| p.to_string()).as_deref() == Some(name) {
+                        return Some((index, 0, true));
+                    }
+                }
+                None
+            }
+
+            /// Template arguments with canonical names in the order of the specification,
+            /// followed by unknown arguments. Binding of the arguments does not change,
+            /// arguments overridden by others are dropped.
+            pub fn canonical_arguments(&self, content: Vec<Element>) -> Vec<Element> {
+                let mut counter = 0;
+                let mut arguments = vec![];
+                for (index, element) in content.into_iter().enumerate() {
+                    let mut argument = match element {
+                        Element::TemplateArgument(argument) => argument,
+                        other => {
+                            arguments.push(((self.attributes.len(), 0, 0, index), false, other));
+                            continue;
+                        }
+                    };
+                    let unnamed = argument.name.trim().is_empty();
+                    argument.name = if unnamed {
+                        counter += 1;
+                        counter.to_string()
+                    } else {
+                        argument.name.trim().to_string()
+                    };
+                    // arguments bound by name follow those bound by position, which they override.
+                    let key = match self.bind_argument(&argument.name.to_lowercase()) {
+                        Some((attribute, number, positional)) => {
+                            let name = self.attributes[attribute].default_name();
+                            argument.name = match number {
+                                0 => name.to_string(),
+                                number => format!("{}{}", name, number),
+                            };
+                            (attribute, number, if positional { 0 } else { 1 }, index)
+                        }
+                        None => (self.attributes.len(), 0, 0, index),
+                    };
+                    arguments.push((key, unnamed, Element::TemplateArgument(argument)));
+                }
+                arguments.sort_by_key(|(key, _, _)| *key);
+
+                // like in MediaWiki, the last argument of a name overrides earlier ones.
+                let mut names = HashSet::new();
+                let mut bound = vec![];
+                for (key, unnamed, element) in arguments.into_iter().rev() {
+                    if let Element::TemplateArgument(ref argument) = element {
+                        if !names.insert(argument.name.clone()) {
+                            continue;
+                        }
+                    }
+                    bound.push((key, unnamed, element));
+                }
+                bound.reverse();
+
+                // arguments which were unnamed stay unnamed if this binds them to their number.
+                let mut unnamed_count = 0;
+                let mut result = vec![];
+                for (_, unnamed, mut element) in bound {
+                    if let Element::TemplateArgument(ref mut argument) = element {
+                        if unnamed && argument.name == (unnamed_count + 1).to_string() {
+                            argument.name = String::new();
+                            unnamed_count += 1;
+                        }
+                    }
+                    result.push(element);
+                }
+                result
+            }
+        }
+
+        /// Rewrites known templates to their canonical form: template and attributes
+        /// are named by their `default_name()` and arguments are ordered like the
+        /// specification, followed by unknown arguments. Names are trimmed.
+        pub fn canonicalize_templates(mut root: Element, settings: ()) -> TResult {
+            if let Element::Template(ref mut template) = root {
+                let name = super::extract_plain_text(&template.name).trim().to_lowercase();
+                if let Some(spec) = super::spec_of(&name) {
+                    template.name = super::text_content(spec.default_name());
+                    let content = std::mem::take(&mut template.content);
+                    template.content = spec.canonical_arguments(content);
+                }
+            }
+            recurse_inplace(&canonicalize_templates, root, settings)
+        }
+    }
+}
+
+/// Error types of template parsing, part of `spec_meta`.
+fn implement_parse_errors() -> TokenStream {
+    quote! {
+        /// Represents failure to convert an attribute value to its specified type.
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        pub struct ConversionError {
+            /// Identifier of the attribute.
+            pub attribute: String,
+            pub expected: AttributeType,
+            /// Position of the offending template argument.
+            pub position: Span,
+            pub cause: String,
+        }
+
+        impl fmt::Display for ConversionError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    f,
+                    "`{}` is not a valid {:?} value: {}",
+                    self.attribute, self.expected, self.cause
+                )
+            }
+        }
+
+        /// Reasons why a template does not fit the specification.
+        /// All variants carry the position of the template.
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        pub enum ParseError {
+            /// The template name is not in the specification.
+            UnknownTemplate {
+                name: String,
+                position: Span,
+            },
+            /// A required attribute is missing. `names` are the argument
+            /// names (or positions) which would have been accepted.
+            MissingAttribute {
+                template: String,
+                attribute: String,
+                names: Vec<String>,
+                position: Span,
+            },
+            /// An attribute is given by multiple arguments, named `names`.
+            DuplicateArgument {
+                template: String,
+                attribute: String,
+                names: Vec<String>,
+                position: Span,
+            },
+            /// An argument does not satisfy the predicate of its attribute.
+            PredicateFailed {
+                template: String,
+                attribute: String,
+                predicate: String,
+                cause: String,
+                position: Span,
+            },
+            /// A repeated attribute has too few or too many values.
+            WrongCount {
+                template: String,
+                attribute: String,
+                min: usize,
+                max: Option<usize>,
+                found: usize,
+                position: Span,
+            },
+            /// An argument does not fit the type of its attribute.
+            Conversion {
+                template: String,
+                error: ConversionError,
+                position: Span,
+            },
+            /// An attribute is given without an attribute it `requires`.
+            MissingRequirement {
+                template: String,
+                attribute: String,
+                requires: String,
+                position: Span,
+            },
+            /// An attribute is given together with one it `conflicts_with`.
+            Conflict {
+                template: String,
+                attribute: String,
+                conflicts_with: String,
+                position: Span,
+            },
+            /// Not exactly one attribute of a `one_of` group is given, `found` are the given ones.
+            OneOf {
+                template: String,
+                attributes: Vec<String>,
+                found: Vec<String>,
+                position: Span,
+            },
+        }
+
+        impl ParseError {
+            /// The position of the template this error occured in.
+            pub fn position(&self) -> &Span {
+                match *self {
+                    ParseError::UnknownTemplate { ref position, .. }
+                    | ParseError::MissingAttribute { ref position, .. }
+                    | ParseError::DuplicateArgument { ref position, .. }
+                    | ParseError::PredicateFailed { ref position, .. }
+                    | ParseError::WrongCount { ref position, .. }
+                    | ParseError::Conversion { ref position, .. }
+                    | ParseError::MissingRequirement { ref position, .. }
+                    | ParseError::Conflict { ref position, .. }
+                    | ParseError::OneOf { ref position, .. } => position,
+                }
+            }
+        }
+
+        impl fmt::Display for ParseError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    ParseError::UnknownTemplate { ref name, .. } => {
+                        write!(f, "unknown template `{}`", name)?
+                    }
+                    ParseError::MissingAttribute { ref template, ref attribute, ref names, .. } => {
+                        write!(
+                            f,
+                            "{} is missing `{}` (as {})",
+                            template, attribute, names.join(", ")
+                        )?
+                    }
+                    ParseError::DuplicateArgument { ref template, ref attribute, ref names, .. } => {
+                        write!(
+                            f,
+                            "{}: `{}` is given multiple times (as {})",
+                            template, attribute, names.join(", ")
+                        )?
+                    }
+                    ParseError::PredicateFailed {
+                        ref template, ref attribute, ref predicate, ref cause, ..
+                    } => {
+                        write!(
+                            f,
+                            "{}: `{}` does not satisfy {}: {}",
+                            template, attribute, predicate, cause
+                        )?
+                    }
+                    ParseError::WrongCount {
+                        ref template, ref attribute, min, max, found, ..
+                    } => {
+                        let max = max.map(|m| m.to_string()).unwrap_or_else(|| "any".into());
+                        write!(
+                            f,
+                            "{}: `{}` needs {} to {} values, found {}",
+                            template, attribute, min, max, found
+                        )?
+                    }
+                    ParseError::Conversion { ref template, ref error, .. } => {
+                        write!(f, "{}: {}", template, error)?
+                    }
+                    ParseError::MissingRequirement {
+                        ref template, ref attribute, ref requires, ..
+                    } => {
+                        write!(f, "{}: `{}` requires `{}`", template, attribute, requires)?
+                    }
+                    ParseError::Conflict { ref template, ref attribute, ref conflicts_with, .. } => {
+                        write!(
+                            f,
+                            "{}: `{}` conflicts with `{}`",
+                            template, attribute, conflicts_with
+                        )?
+                    }
+                    ParseError::OneOf { ref template, ref attributes, ref found, .. } => {
+                        let quoted = |ids: &[String]| -> Vec<String> {
+                            ids.iter().map(|id| format!("`{}`", id)).collect()
+                        };
+                        let found = match found.len() {
+                            0 => "none".to_string(),
+                            _ => quoted(found).join(", "),
+                        };
+                        write!(
+                            f,
+                            "{}: exactly one of {} is needed, found {}",
+                            template, quoted(attributes).join(", "), found
+                        )?
+                    }
+                }
+                write!(f, " at line {}", self.position().start.line)
+            }
+        }
+    }
+}
+
+fn read_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut file = fs::File::open(path.as_ref())?;
+    let mut string = String::new();
+    file.read_to_string(&mut string)?;
+    Ok(string)
+}
+
+/// Reports errors in the spec file as compile errors at the macro invocation.
+fn spec_errors(path: &LitStr, errors: &[SpecError]) -> proc_macro::TokenStream {
+    let file = path.value();
+    let span = path.span();
+    let messages = errors
+        .iter()
+        .map(|error| LitStr::new(&format!("{}:{}: {}", file, error.line, error.message), span));
+    let tokens = quote_spanned! {span=>
+        #( compile_error!(#messages); )*
+    };
+    tokens.into()
+}
+
+#[proc_macro]
+pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let path_lit: LitStr = match syn::parse(input) {
+        Ok(path_lit) => path_lit,
+        Err(_) => {
+            let tokens = quote! {
+                compile_error!("template_spec! expects the path of the spec file as string literal!");
+            };
+            return tokens.into();
+        }
+    };
+    let path = path_lit.value();
+
+    let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".into());
+    let path = Path::new(&root).join(&path);
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name,
+        None => {
+            let error = SpecError::new(0, "spec attribute should point to a file");
+            return spec_errors(&path_lit, &[error]);
+        }
+    };
+
+    let data = match read_file(&path) {
+        Ok(data) => data,
+        Err(error) => {
+            let error = SpecError::new(0, format!("error opening {:?}: {}", file_name, error));
+            return spec_errors(&path_lit, &[error]);
+        }
+    };
+    let (templates, mut errors) = load_spec(&data);
+    let check_predicate = |predicate: &str| parse_predicate(predicate).map(|_| ());
+    errors.append(&mut check_spec(&templates, &check_predicate));
+    if !errors.is_empty() {
+        sort_errors(&mut errors);
+        return spec_errors(&path_lit, &errors);
+    }
+
+    let template_id = implement_template_id(&templates);
+    let template_impls = implement_templates(&templates);
+    let spec_func = implement_spec_list(&templates);
     let template_parsing = implement_template_parsing(&templates);
+    let parse_errors = implement_parse_errors();
+    let predicate_fns = implement_predicate_fns(&templates);
+    let validation = implement_validation();
+    let deprecation = implement_deprecation();
+    let canonicalization = implement_canonicalization();
+    let occurrences = implement_occurrences();
+    let predicates = implement_predicates();
+    let predicate_library = implement_predicate_library();
+    let template_data = implement_template_data();
+    let markdown = reference_markdown(&templates);
+    let html = reference_html(&templates);
+    let schema = format!("{:#}", json_schema(&templates));
 
     let implementation = quote! {
 
@@ -346,8 +2297,11 @@ pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream
         /// Types and utils used in the documentation.
         pub mod spec_meta {
 
+            use std::collections::{HashMap, HashSet};
+            use std::fmt;
             use std::io;
-            use mediawiki_parser::{Element, Traversion};
+            use mediawiki_parser::transformations::{recurse_inplace, TResult};
+            use mediawiki_parser::{Element, Error, Span, Template, TemplateArgument, Traversion};
             use serde_derive::{Serialize, Deserialize};
 
             /// Specifies wether a template represents a logical unit (`Block`)
@@ -359,6 +2313,13 @@ pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                 Inline
             }
 
+            /// Where in a document a template has to be placed.
+            #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+            pub enum DocumentPosition {
+                First,
+                Last
+            }
+
             /// Template attributes can have different priorities.
             #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
             pub enum Priority {
@@ -366,82 +2327,105 @@ pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                 Optional
             }
 
-            /// Represents failure of a predicate check.
-            pub struct PredError<'e> {
-                pub tree: Option<&'e Element>,
-                pub cause: String,
+            /// The type of value a template attribute holds.
+            /// Attributes of type `Elements` are not converted.
+            #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+            pub enum AttributeType {
+                Elements,
+                Text,
+                Integer,
+                Boolean,
+                Enum,
+                Formula,
+                File,
             }
 
-            /// Result of a predicate check.
-            pub type PredResult<'e> = Result<(), PredError<'e>>;
-            /// A function to determine wether a given element is allowed.
-            pub type Predicate = Fn(&[Element]) -> PredResult + Sync;
+            #parse_errors
 
-            /// Checks a predicate for a given input tree.
-            struct TreeChecker<'path, 'e> {
-                pub path: Vec<&'path Element>,
-                pub result: PredResult<'e>,
-            }
+            #validation
 
-            impl <'e, 'p: 'e> Traversion<'e, &'p Predicate> for TreeChecker<'e, 'e> {
+            #deprecation
 
-                fn path_push(&mut self, root: &'e Element) {
-                    self.path.push(root);
-                }
-                fn path_pop(&mut self) -> Option<&'e Element> {
-                    self.path.pop()
-                }
-                fn get_path(&self) -> &Vec<&'e Element> {
-                    &self.path
-                }
+            #canonicalization
 
-                fn work_vec(
-                    &mut self,
-                    root: &'e [Element],
-                    predicate: &'p Predicate,
-                    _: &mut io::Write
-                ) -> io::Result<bool> {
-                    if self.result.is_err() {
-                        return Ok(false)
-                    }
-                    self.result = (predicate)(root);
-                    Ok(true)
-                }
+            #occurrences
+
+            #predicates
+
+            #predicate_library
+
+            #template_data
+
+            /// An authoring guide for the templates of the specification, in Markdown.
+            pub fn reference_markdown() -> &'static str {
+                #markdown
             }
 
-            /// Checks a predicate recursively.
-            pub fn always<'e, 'p: 'e>(root: &'e [Element], predicate: &'p Predicate)
-                -> PredResult<'e>
-            {
-                let mut checker = TreeChecker {
-                    path: vec![],
-                    result: Ok(()),
-                };
-                checker.result = Ok(());
-                checker.run_vec(&root, predicate, &mut vec![])
-                    .expect("error checking predicate!");
-                checker.result
+            /// An authoring guide for the templates of the specification, in HTML.
+            pub fn reference_html() -> &'static str {
+                #html
             }
 
+            /// The JSON Schema of serialized `KnownTemplate`s.
+            pub fn json_schema() -> &'static str {
+                #schema
+            }
 
             /// Represents a (semantic) template.
             #[derive(Clone, Serialize)]
             pub struct TemplateSpec<'p> {
+                pub identifier: String,
                 pub names: Vec<String>,
+                /// Old names, which should be replaced by `default_name()`.
+                pub deprecated_names: Vec<String>,
                 pub description: String,
                 pub format: Format,
+                /// Identifiers of templates this template may only appear in.
+                pub allowed_in: Vec<String>,
+                /// Identifiers of templates this template may not appear in.
+                pub forbidden_in: Vec<String>,
+                /// Identifiers of the only templates allowed directly inside this template.
+                pub allowed_children: Option<Vec<String>>,
+                /// Groups of attribute identifiers, exactly one of each group must be given.
+                pub one_of: Vec<Vec<String>>,
+                /// Maximum number of occurrences of this template in a document.
+                pub max_per_document: Option<usize>,
+                /// Wether this template must be at the start or the end of a document.
+                pub position: Option<DocumentPosition>,
                 pub attributes: Vec<AttributeSpec<'p>>,
             }
 
             /// Represents the specification of an attribute (or argument) of a template.
             #[derive(Clone, Serialize)]
             pub struct AttributeSpec<'p> {
+                pub identifier: String,
                 pub names: Vec<String>,
+                /// Old names, which should be replaced by `default_name()`.
+                pub deprecated_names: Vec<String>,
                 pub description: String,
                 pub priority: Priority,
                 #[serde(skip)]
                 pub predicate: &'p Predicate,
                 pub predicate_name: String,
+                #[serde(rename = "type")]
+                pub value_type: AttributeType,
+                /// Allowed values of an `Enum` attribute.
+                pub values: Vec<String>,
+                /// Position of an unnamed template argument filling this attribute.
+                pub position: Option<usize>,
+                /// Value of this attribute if it is missing, as given in the spec.
+                pub default: Option<String>,
+                /// Wether this attribute collects all arguments named by one
+                /// of `names` followed by a number, like `item1` to `itemN`.
+                pub repeated: bool,
+                /// Minimum number of values of a repeated attribute.
+                pub min_count: usize,
+                /// Maximum number of values of a repeated attribute.
+                pub max_count: Option<usize>,
+                /// Identifiers of attributes which must be given if this attribute is given.
+                pub requires: Vec<String>,
+                /// Identifiers of attributes which cannot be given together with this attribute.
+                pub conflicts_with: Vec<String>,
             }
 
             impl<'p> TemplateSpec<'p> {
@@ -460,6 +2444,17 @@ pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                 }
             }
 
+            /// A template argument which does not belong to any attribute.
+            #[derive(Debug, Clone, PartialEq, Serialize)]
+            pub struct UnknownArgument<'e> {
+                /// Name of the argument, unnamed arguments are numbered.
+                pub name: String,
+                /// The closest attribute name, if `name` looks like a typo of it.
+                pub suggestion: Option<String>,
+                pub value: &'e [Element],
+                pub position: Span,
+            }
+
             /// Represents a concrete value of a template attribute.
             #[derive(Debug, Clone, PartialEq, Serialize)]
             pub struct Attribute<'e> {
@@ -473,18 +2468,30 @@ pub fn template_spec(input: proc_macro::TokenStream) -> proc_macro::TokenStream
 
         /// Get the specification of a specific template, if it exists.
         pub fn spec_of<'p>(name: &str) -> Option<TemplateSpec<'p>> {
-            let name = name.trim().to_lowercase();
-            for spec in spec() {
-                if spec.names.contains(&name) {
-                    return Some(spec)
+            // the specification and its template names are built on first use.
+            static SPECS: std::sync::OnceLock<(
+                Vec<TemplateSpec<'static>>,
+                std::collections::HashMap<String, usize>,
+            )> = std::sync::OnceLock::new();
+            let (specs, names) = SPECS.get_or_init(|| {
+                let specs = spec();
+                let mut names = std::collections::HashMap::new();
+                for (index, spec) in specs.iter().enumerate() {
+                    for name in spec.names.iter().chain(&spec.deprecated_names) {
+                        names.entry(name.clone()).or_insert(index);
+                    }
                 }
-            }
-            None
+                (specs, names)
+            });
+            names
+                .get(&name.trim().to_lowercase())
+                .map(|index| specs[*index].clone())
         }
 
         #template_id
         #spec_func
         #template_parsing
+        #( #predicate_fns )*
         #( #template_impls )*
     };
     implementation.into()
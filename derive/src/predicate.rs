@@ -0,0 +1,78 @@
+//! Code for predicate references of the specification, like `no_templates`,
+//! `max_length(200)` or `crate::preds::only_inline`.
+//!
+//! References are parsed and checked by `mwparser_utils_meta::predicate`.
+//! Other calls than those of the library in `spec_meta` pass their arguments
+//! after the checked content, e.g. `min_items(3)` calls `min_items(root, 3)`.
+
+use mwparser_utils_meta::predicate::{library_params, negation_cause, Params};
+pub use mwparser_utils_meta::predicate::{parse_predicate, PredicateArg, PredicateRef};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::Ident;
+
+/// Tokens for a predicate argument, defining functions for nested calls in `items`.
+fn argument_tokens(arg: &PredicateArg, items: &mut Vec<TokenStream>) -> TokenStream {
+    match *arg {
+        PredicateArg::Lit(ref lit) => quote! { #lit },
+        PredicateArg::Predicate(PredicateRef::Path(ref path)) => quote! { &#path },
+        PredicateArg::Predicate(ref predicate) => {
+            let name = Ident::new(&format!("arg{}", items.len()), Span::call_site());
+            items.push(predicate.implement(&name));
+            quote! { &#name }
+        }
+    }
+}
+
+/// Generated functions for predicate references.
+pub trait PredicateCode {
+    /// A path to the predicate function, `name` for calls.
+    fn function(&self, name: &Ident) -> TokenStream;
+
+    /// A function `name` calling this predicate with its arguments.
+    fn implement(&self, name: &Ident) -> TokenStream;
+}
+
+impl PredicateCode for PredicateRef {
+    fn function(&self, name: &Ident) -> TokenStream {
+        match *self {
+            PredicateRef::Path(ref path) => quote! { #path },
+            PredicateRef::Call(..) => quote! { #name },
+        }
+    }
+
+    fn implement(&self, name: &Ident) -> TokenStream {
+        let (path, args) = match *self {
+            PredicateRef::Path(ref path) => {
+                return quote! {
+                    fn #name(root: &[Element]) -> PredResult {
+                        #path(root)
+                    }
+                }
+            }
+            PredicateRef::Call(ref path, ref args) => (path, args),
+        };
+        let mut items = vec![];
+        let tokens: Vec<TokenStream> = args
+            .iter()
+            .map(|arg| argument_tokens(arg, &mut items))
+            .collect();
+        let call = match library_params(path) {
+            Some(Params::Kinds) | Some(Params::Predicates) => quote! {
+                #path(root, &[ #( #tokens ),* ])
+            },
+            Some(Params::Negation) => {
+                let predicate = &tokens[0];
+                let cause = negation_cause(args);
+                quote! { #path(root, #predicate, #cause) }
+            }
+            _ => quote! { #path(root, #( #tokens ),*) },
+        };
+        quote! {
+            fn #name(root: &[Element]) -> PredResult {
+                #( #items )*
+                #call
+            }
+        }
+    }
+}
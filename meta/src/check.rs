@@ -0,0 +1,503 @@
+//! Consistency checks for a template specification.
+//!
+//! Predicates are resolved differently by the macro and at runtime,
+//! so they are checked by a function given to `check_spec`.
+
+use crate::load::SpecError;
+use crate::spec::{SpecAttribute, SpecPriority, SpecTemplate, SpecType};
+use std::collections::HashMap;
+use syn::Ident;
+
+/// Fields every generated template struct has and methods of the template builders,
+/// unavailable as attribute identifiers.
+pub const RESERVED_NAMES: [&str; 8] = [
+    "identifier",
+    "names",
+    "format",
+    "description",
+    "present",
+    "unknown",
+    "build",
+    "into_template",
+];
+
+/// Types the generated code defines or imports next to the template structs,
+/// unavailable as template identifiers.
+pub const RESERVED_TEMPLATE_NAMES: [&str; 20] = [
+    "Element",
+    "Template",
+    "Serialize",
+    "KnownTemplate",
+    "PredError",
+    "PredResult",
+    "Predicate",
+    "PredFailure",
+    "Severity",
+    "Diagnostic",
+    "ConversionError",
+    "ParseError",
+    "Format",
+    "DocumentPosition",
+    "Priority",
+    "AttributeType",
+    "TemplateSpec",
+    "AttributeSpec",
+    "UnknownArgument",
+    "Attribute",
+];
+
+/// Wether `name` can be used as a rust identifier.
+pub fn is_identifier(name: &str) -> bool {
+    syn::parse_str::<Ident>(name).is_ok()
+}
+
+/// Reports an error if `key` was already seen, remembers it otherwise.
+fn check_unique(
+    seen: &mut HashMap<String, usize>,
+    key: &str,
+    line: usize,
+    kind: &str,
+    errors: &mut Vec<SpecError>,
+) {
+    if let Some(first) = seen.get(key) {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "duplicate {} {:?}, first defined at line {}!",
+                kind, key, first
+            ),
+        ));
+    } else {
+        seen.insert(key.to_string(), line);
+    }
+}
+
+/// Reads a boolean the same way `parse_boolean` does at runtime.
+pub fn boolean_value(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "true" | "yes" | "ja" | "1" => Some(true),
+        "false" | "no" | "nein" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Checks that the default of an attribute fits its type.
+pub fn check_default(attribute: &SpecAttribute, default: &str) -> Result<(), String> {
+    if attribute.priority == SpecPriority::Required {
+        return Err("required attributes cannot have a default!".into());
+    }
+    let value = default.trim();
+    match attribute.value_type {
+        SpecType::Integer if value.parse::<i64>().is_err() => {
+            Err(format!("default {:?} is not an integer!", value))
+        }
+        SpecType::Boolean if boolean_value(value).is_none() => {
+            Err(format!("default {:?} is not a boolean value!", value))
+        }
+        SpecType::Enum
+            if !attribute
+                .values
+                .iter()
+                .any(|v| v.trim().to_lowercase() == value.to_lowercase()) =>
+        {
+            Err(format!("default {:?} is not one of the values!", value))
+        }
+        SpecType::File | SpecType::Formula if value.is_empty() => {
+            Err("default must not be empty!".into())
+        }
+        SpecType::Elements => match mediawiki_parser::parse(default) {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                // the first line of parser errors describes the problem.
+                let message = error.to_string();
+                let cause = message.lines().next().unwrap_or_default();
+                Err(format!(
+                    "default {:?} is not valid wikitext: {}",
+                    value, cause
+                ))
+            }
+        },
+        _ => Ok(()),
+    }
+}
+
+fn check_attribute(
+    attribute: &SpecAttribute,
+    check_predicate: &dyn Fn(&str) -> Result<(), String>,
+    errors: &mut Vec<SpecError>,
+) {
+    let line = attribute.line;
+    if !is_identifier(&attribute.identifier) {
+        errors.push(SpecError::new(
+            line,
+            format!("{:?} is not a valid identifier!", attribute.identifier),
+        ));
+    }
+
+    if attribute.identifier.chars().any(|c| c.is_uppercase()) {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: attribute identifiers should be lowercase!",
+                attribute.identifier
+            ),
+        ));
+    }
+
+    if RESERVED_NAMES.contains(&attribute.identifier.as_str()) {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?} is reserved and cannot be used as attribute identifier!",
+                attribute.identifier
+            ),
+        ));
+    }
+
+    if attribute.position == Some(0) {
+        errors.push(SpecError::new(
+            line,
+            format!("{:?}: argument positions start at 1!", attribute.identifier),
+        ));
+    }
+
+    if attribute.names.is_empty() && attribute.position.is_none() {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: attributes must have at least one name or a position!",
+                attribute.identifier
+            ),
+        ));
+    }
+
+    if attribute.names.is_empty() && !attribute.deprecated_names.is_empty() {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: attributes with deprecated names need a name replacing them!",
+                attribute.identifier
+            ),
+        ));
+    }
+
+    if let Err(message) = check_predicate(&attribute.predicate) {
+        errors.push(SpecError::new(
+            line,
+            format!("{:?}: {}", attribute.identifier, message),
+        ));
+    }
+
+    if attribute.repeated {
+        let incompatible = [
+            ("a default", attribute.default.is_some()),
+            ("a position", attribute.position.is_some()),
+        ];
+        for (what, present) in &incompatible {
+            if *present {
+                errors.push(SpecError::new(
+                    line,
+                    format!(
+                        "{:?}: repeated attributes cannot have {}!",
+                        attribute.identifier, what
+                    ),
+                ));
+            }
+        }
+        if attribute.max_count.map(|max| max < attribute.min_count()) == Some(true) {
+            errors.push(SpecError::new(
+                line,
+                format!(
+                    "{:?}: max_count is smaller than min_count!",
+                    attribute.identifier
+                ),
+            ));
+        }
+    } else if attribute.min_count.is_some() || attribute.max_count.is_some() {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: only repeated attributes can have a min_count or max_count!",
+                attribute.identifier
+            ),
+        ));
+    }
+
+    if let Some(ref default) = attribute.default {
+        if let Err(message) = check_default(attribute, default) {
+            errors.push(SpecError::new(
+                line,
+                format!("{:?}: {}", attribute.identifier, message),
+            ));
+        }
+    }
+
+    match (attribute.value_type, attribute.values.is_empty()) {
+        (SpecType::Enum, true) => errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: enum attributes must have at least one value!",
+                attribute.identifier
+            ),
+        )),
+        (SpecType::Enum, false) => {
+            let mut values = HashMap::new();
+            for value in &attribute.values {
+                let value = value.trim().to_lowercase();
+                check_unique(&mut values, &value, line, "enum value", errors);
+            }
+        }
+        (_, false) => errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: only enum attributes can have values!",
+                attribute.identifier
+            ),
+        )),
+        (_, true) => (),
+    }
+}
+
+fn check_template(
+    template: &SpecTemplate,
+    check_predicate: &dyn Fn(&str) -> Result<(), String>,
+    errors: &mut Vec<SpecError>,
+) {
+    let line = template.line;
+    if !is_identifier(&template.identifier) {
+        errors.push(SpecError::new(
+            line,
+            format!("{:?} is not a valid identifier!", template.identifier),
+        ));
+    }
+
+    let first_uppercase = template
+        .identifier
+        .chars()
+        .next()
+        .map(|c| c.is_uppercase())
+        .unwrap_or(false);
+
+    if !first_uppercase {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "first character of identifier {:?} should be uppercase!",
+                template.identifier
+            ),
+        ));
+    }
+
+    if RESERVED_TEMPLATE_NAMES.contains(&template.identifier.as_str()) {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?} is reserved and cannot be used as template identifier!",
+                template.identifier
+            ),
+        ));
+    }
+
+    if template.names.is_empty() {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: templates must have at least one name!",
+                template.identifier
+            ),
+        ));
+    }
+
+    let mut identifiers = HashMap::new();
+    let mut names = HashMap::new();
+    for attribute in &template.attributes {
+        check_attribute(attribute, check_predicate, errors);
+        check_unique(
+            &mut identifiers,
+            &attribute.identifier,
+            attribute.line,
+            "attribute identifier",
+            errors,
+        );
+        // positions are bound to numeric names, so they may not collide.
+        let position = attribute.position.map(|p| p.to_string());
+        for name in attribute.accepted_names().iter().chain(position.iter()) {
+            check_unique(
+                &mut names,
+                name,
+                attribute.line,
+                "attribute name or position",
+                errors,
+            );
+        }
+    }
+    check_constraints(template, errors);
+
+    if template.max_per_document == Some(0) {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: max_per_document must be at least 1!",
+                template.identifier
+            ),
+        ));
+    }
+
+    if template.position.is_some() && !template.allowed_in.is_empty() {
+        errors.push(SpecError::new(
+            line,
+            format!(
+                "{:?}: templates with a position cannot have allowed_in!",
+                template.identifier
+            ),
+        ));
+    }
+}
+
+/// Checks that attribute constraints refer to attributes of the same template.
+fn check_constraints(template: &SpecTemplate, errors: &mut Vec<SpecError>) {
+    let exists = |id: &String| template.attributes.iter().any(|a| a.identifier == *id);
+    for attribute in &template.attributes {
+        let rules = [
+            ("requires", &attribute.requires),
+            ("conflicts_with", &attribute.conflicts_with),
+        ];
+        for (key, references) in &rules {
+            for reference in references.iter() {
+                let message = if *reference == attribute.identifier {
+                    format!("{} cannot refer to the attribute itself!", key)
+                } else if !exists(reference) {
+                    format!("unknown attribute {:?} in {}!", reference, key)
+                } else {
+                    continue;
+                };
+                errors.push(SpecError::new(
+                    attribute.line,
+                    format!("{:?}: {}", attribute.identifier, message),
+                ));
+            }
+        }
+        for reference in &attribute.requires {
+            if attribute.conflicts_with.contains(reference) {
+                errors.push(SpecError::new(
+                    attribute.line,
+                    format!(
+                        "{:?}: {:?} is in requires and conflicts_with!",
+                        attribute.identifier, reference
+                    ),
+                ));
+            }
+        }
+    }
+    for group in &template.one_of {
+        if group.len() < 2 {
+            errors.push(SpecError::new(
+                template.line,
+                format!(
+                    "{:?}: one_of groups need at least two attributes!",
+                    template.identifier
+                ),
+            ));
+        }
+        for reference in group {
+            let attribute = template
+                .attributes
+                .iter()
+                .find(|a| a.identifier == *reference);
+            let message = match attribute {
+                None => format!("unknown attribute {:?} in one_of!", reference),
+                Some(a) if a.priority == SpecPriority::Required => {
+                    format!("{:?} in one_of must be optional!", reference)
+                }
+                Some(_) => continue,
+            };
+            errors.push(SpecError::new(
+                template.line,
+                format!("{:?}: {}", template.identifier, message),
+            ));
+        }
+    }
+}
+
+/// Checks that the placement rules of a template refer to existing templates.
+fn check_placement(
+    template: &SpecTemplate,
+    identifiers: &HashMap<String, usize>,
+    errors: &mut Vec<SpecError>,
+) {
+    let children = template.allowed_children.iter().flatten();
+    let rules = [
+        ("allowed_in", template.allowed_in.iter().collect::<Vec<_>>()),
+        ("forbidden_in", template.forbidden_in.iter().collect()),
+        ("allowed_children", children.collect()),
+    ];
+    for (key, references) in &rules {
+        for reference in references {
+            if !identifiers.contains_key(*reference) {
+                errors.push(SpecError::new(
+                    template.line,
+                    format!(
+                        "{:?}: unknown template {:?} in {}!",
+                        template.identifier, reference, key
+                    ),
+                ));
+            }
+        }
+    }
+    for reference in &template.allowed_in {
+        if template.forbidden_in.contains(reference) {
+            errors.push(SpecError::new(
+                template.line,
+                format!(
+                    "{:?}: {:?} is in allowed_in and forbidden_in!",
+                    template.identifier, reference
+                ),
+            ));
+        }
+    }
+}
+
+/// Checks a whole specification, returning all errors found.
+///
+/// `check_predicate` checks a predicate reference, returning a description of the problem.
+pub fn check_spec(
+    templates: &[SpecTemplate],
+    check_predicate: &dyn Fn(&str) -> Result<(), String>,
+) -> Vec<SpecError> {
+    let mut errors = vec![];
+    let mut identifiers = HashMap::new();
+    let mut names = HashMap::new();
+    for template in templates {
+        check_template(template, check_predicate, &mut errors);
+        check_unique(
+            &mut identifiers,
+            &template.identifier,
+            template.line,
+            "template identifier",
+            &mut errors,
+        );
+        for name in &template.accepted_names() {
+            check_unique(
+                &mut names,
+                name,
+                template.line,
+                "template name",
+                &mut errors,
+            );
+        }
+    }
+    for template in templates {
+        check_placement(template, &identifiers, &mut errors);
+        let builder = format!("{}Builder", template.identifier);
+        if let Some(line) = identifiers.get(&builder) {
+            errors.push(SpecError::new(
+                *line,
+                format!(
+                    "{:?} is the name of the builder of {:?}!",
+                    builder, template.identifier
+                ),
+            ));
+        }
+    }
+    errors
+}
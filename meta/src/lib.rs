@@ -0,0 +1,18 @@
+//! The template specification format, shared by the `template_spec!` macro
+//! and the runtime `SpecRegistry` of `mwparser_utils`.
+
+pub mod check;
+pub mod load;
+pub mod predicate;
+pub mod reference;
+pub mod schema;
+pub mod spec;
+
+#[cfg(test)]
+mod test;
+
+pub use crate::check::check_spec;
+pub use crate::load::{load_spec, sort_errors, SpecError};
+pub use crate::reference::{reference_html, reference_markdown};
+pub use crate::schema::json_schema;
+pub use crate::spec::*;
@@ -0,0 +1,460 @@
+//! Loading of the YAML specification, keeping track of source lines.
+//!
+//! `serde_yaml` stops at the first error and only knows locations for syntax errors,
+//! so the spec is first read into a tree of line-annotated nodes. Templates and attributes
+//! are then deserialized one by one, which allows reporting all of their errors at once.
+//!
+//! Attribute groups and `extends` are expanded here, so the templates returned
+//! contain all of their attributes.
+
+use crate::spec::{SpecAttribute, SpecGroup, SpecTemplate};
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+use yaml_rust::Yaml;
+
+/// A problem found in the specification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecError {
+    /// Line in the spec file, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl SpecError {
+    pub fn new<S: Into<String>>(line: usize, message: S) -> SpecError {
+        SpecError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Sorts errors by line and removes duplicates, like those of attribute groups,
+/// which are checked for every template including them.
+pub fn sort_errors(errors: &mut Vec<SpecError>) {
+    let mut seen = HashSet::new();
+    errors.retain(|error| seen.insert((error.line, error.message.clone())));
+    errors.sort_by_key(|error| error.line);
+}
+
+/// A YAML node annotated with the line it starts at.
+#[derive(Debug, Clone)]
+struct Node {
+    line: usize,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Scalar(String, TScalarStyle),
+    Sequence(Vec<Node>),
+    Mapping(Vec<(Node, Node)>),
+}
+
+/// A collection node which is not completely read yet.
+struct OpenNode {
+    line: usize,
+    anchor: usize,
+    mapping: bool,
+    children: Vec<Node>,
+}
+
+/// Builds a tree of `Node` from YAML parser events.
+#[derive(Default)]
+struct NodeBuilder {
+    open: Vec<OpenNode>,
+    anchors: HashMap<usize, Node>,
+    root: Option<Node>,
+}
+
+impl NodeBuilder {
+    fn insert(&mut self, node: Node, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.root = Some(node),
+        }
+    }
+
+    fn close(&mut self) {
+        let open = match self.open.pop() {
+            Some(open) => open,
+            None => return,
+        };
+        let kind = if open.mapping {
+            let mut entries = vec![];
+            let mut children = open.children.into_iter();
+            while let (Some(key), Some(value)) = (children.next(), children.next()) {
+                entries.push((key, value));
+            }
+            NodeKind::Mapping(entries)
+        } else {
+            NodeKind::Sequence(open.children)
+        };
+        let node = Node {
+            line: open.line,
+            kind,
+        };
+        self.insert(node, open.anchor);
+    }
+}
+
+impl MarkedEventReceiver for NodeBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::SequenceStart(anchor) | Event::MappingStart(anchor) => {
+                self.open.push(OpenNode {
+                    line: mark.line(),
+                    anchor,
+                    mapping: event != Event::SequenceStart(anchor),
+                    children: vec![],
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => self.close(),
+            Event::Scalar(value, style, anchor, _) => {
+                let node = Node {
+                    line: mark.line(),
+                    kind: NodeKind::Scalar(value, style),
+                };
+                self.insert(node, anchor);
+            }
+            Event::Alias(anchor) => {
+                let node = self.anchors.get(&anchor).cloned().unwrap_or(Node {
+                    line: mark.line(),
+                    kind: NodeKind::Scalar("~".into(), TScalarStyle::Plain),
+                });
+                self.insert(node, 0);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Node {
+    /// Converts this node to a `serde_yaml` value, resolving plain scalars.
+    fn to_value(&self) -> Value {
+        match self.kind {
+            NodeKind::Scalar(ref value, TScalarStyle::Plain) => match Yaml::from_str(value) {
+                Yaml::Null => Value::Null,
+                Yaml::Boolean(b) => Value::Bool(b),
+                Yaml::Integer(i) => Value::Number(i.into()),
+                Yaml::Real(ref r) => match r.parse::<f64>() {
+                    Ok(f) => Value::Number(f.into()),
+                    Err(_) => Value::String(value.clone()),
+                },
+                _ => Value::String(value.clone()),
+            },
+            NodeKind::Scalar(ref value, _) => Value::String(value.clone()),
+            NodeKind::Sequence(ref items) => {
+                Value::Sequence(items.iter().map(Node::to_value).collect())
+            }
+            NodeKind::Mapping(ref entries) => Value::Mapping(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.to_value(), value.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn scalar(&self) -> Option<&str> {
+        match self.kind {
+            NodeKind::Scalar(ref value, _) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a key of a mapping node.
+    fn get(&self, key: &str) -> Option<&Node> {
+        if let NodeKind::Mapping(ref entries) = self.kind {
+            for (k, v) in entries {
+                if k.scalar() == Some(key) {
+                    return Some(v);
+                }
+            }
+        }
+        None
+    }
+
+    /// Finds the line of a key or value quoted in a serde error message,
+    /// e.g. "unknown field `foo`". Falls back to the line of this node.
+    fn locate(&self, message: &str) -> usize {
+        let quoted = message.split('`').nth(1);
+        if let (NodeKind::Mapping(ref entries), Some(quoted)) = (&self.kind, quoted) {
+            for (key, value) in entries {
+                if key.scalar() == Some(quoted) {
+                    return key.line;
+                }
+                if value.scalar() == Some(quoted) {
+                    return value.line;
+                }
+            }
+        }
+        self.line
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, value: Value) -> Result<T, SpecError> {
+        serde_yaml::from_value(value).map_err(|error| {
+            let message = error.to_string();
+            SpecError::new(self.locate(&message), message)
+        })
+    }
+}
+
+/// Deserializes the attributes of a template or group node separately, to report errors
+/// for each of them. Returns the value of the node without attributes.
+fn load_attributes(
+    node: &Node,
+    attributes: &mut Vec<SpecAttribute>,
+    errors: &mut Vec<SpecError>,
+) -> Value {
+    let mut value = node.to_value();
+    if let (Value::Mapping(ref mut map), Some(attr_node)) = (&mut value, node.get("attributes")) {
+        if let NodeKind::Sequence(ref items) = attr_node.kind {
+            for item in items {
+                match item.deserialize::<SpecAttribute>(item.to_value()) {
+                    Ok(mut attribute) => {
+                        attribute.line = item.line;
+                        attributes.push(attribute);
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+            map.insert(Value::String("attributes".into()), Value::Sequence(vec![]));
+        }
+    }
+    value
+}
+
+fn load_template(node: &Node) -> Result<SpecTemplate, Vec<SpecError>> {
+    let mut errors = vec![];
+    let mut attributes = vec![];
+    let value = load_attributes(node, &mut attributes, &mut errors);
+
+    match node.deserialize::<SpecTemplate>(value) {
+        Ok(mut template) => {
+            template.line = node.line;
+            template.attributes = attributes;
+            if errors.is_empty() {
+                Ok(template)
+            } else {
+                Err(errors)
+            }
+        }
+        Err(error) => {
+            errors.insert(0, error);
+            Err(errors)
+        }
+    }
+}
+
+fn load_group(node: &Node) -> Result<SpecGroup, Vec<SpecError>> {
+    let mut errors = vec![];
+    let mut attributes = vec![];
+    let value = load_attributes(node, &mut attributes, &mut errors);
+
+    match node.deserialize::<SpecGroup>(value) {
+        Ok(mut group) => {
+            group.line = node.line;
+            group.attributes = attributes;
+            if errors.is_empty() {
+                Ok(group)
+            } else {
+                Err(errors)
+            }
+        }
+        Err(error) => {
+            errors.insert(0, error);
+            Err(errors)
+        }
+    }
+}
+
+/// Wether two attributes are defined the same way, wherever they are defined.
+fn same_definition(a: &SpecAttribute, b: &SpecAttribute) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.line = 0;
+    b.line = 0;
+    a == b
+}
+
+/// Expands attribute groups and `extends` of templates.
+struct Expansion<'s> {
+    templates: &'s [SpecTemplate],
+    groups: HashMap<String, &'s SpecGroup>,
+    /// Expanded attributes by template identifier.
+    expanded: HashMap<String, Vec<SpecAttribute>>,
+    errors: Vec<SpecError>,
+}
+
+impl<'s> Expansion<'s> {
+    /// Adds attributes from `source` to the attributes of `template`,
+    /// remembering where each attribute comes from.
+    fn merge(
+        &mut self,
+        template: &SpecTemplate,
+        attributes: &mut Vec<(SpecAttribute, String)>,
+        additions: &[SpecAttribute],
+        source: &str,
+    ) {
+        for addition in additions {
+            // duplicates from the same source are left to `check_spec`.
+            let existing = attributes
+                .iter()
+                .find(|(a, from)| a.identifier == addition.identifier && from != source);
+            match existing {
+                Some((attribute, _)) if same_definition(attribute, addition) => (),
+                Some((_, ref first)) => self.errors.push(SpecError::new(
+                    addition.line,
+                    format!(
+                        "{:?}: attribute {:?} from {} conflicts with the one from {}!",
+                        template.identifier, addition.identifier, source, first
+                    ),
+                )),
+                None => attributes.push((addition.clone(), source.to_string())),
+            }
+        }
+    }
+
+    /// The attributes of a template with those of its parent and groups,
+    /// `visiting` are the templates extending it.
+    fn expand(
+        &mut self,
+        template: &'s SpecTemplate,
+        visiting: &mut Vec<String>,
+    ) -> Vec<SpecAttribute> {
+        if let Some(attributes) = self.expanded.get(&template.identifier) {
+            return attributes.clone();
+        }
+        let mut attributes = vec![];
+        if let Some(ref parent_id) = template.extends {
+            let parent = self.templates.iter().find(|t| t.identifier == *parent_id);
+            match parent {
+                _ if visiting.contains(parent_id) || *parent_id == template.identifier => {
+                    self.errors.push(SpecError::new(
+                        template.line,
+                        format!("{:?}: extends itself!", template.identifier),
+                    ))
+                }
+                Some(parent) => {
+                    visiting.push(template.identifier.clone());
+                    let inherited = self.expand(parent, visiting);
+                    visiting.pop();
+                    let source = format!("template {:?}", parent_id);
+                    self.merge(template, &mut attributes, &inherited, &source);
+                }
+                None => self.errors.push(SpecError::new(
+                    template.line,
+                    format!(
+                        "{:?}: unknown template {:?} in extends!",
+                        template.identifier, parent_id
+                    ),
+                )),
+            }
+        }
+        for name in &template.groups {
+            match self.groups.get(name).cloned() {
+                Some(group) => {
+                    let source = format!("group {:?}", name);
+                    self.merge(template, &mut attributes, &group.attributes, &source);
+                }
+                None => self.errors.push(SpecError::new(
+                    template.line,
+                    format!(
+                        "{:?}: unknown attribute group {:?}!",
+                        template.identifier, name
+                    ),
+                )),
+            }
+        }
+        let source = format!("template {:?}", template.identifier);
+        self.merge(template, &mut attributes, &template.attributes, &source);
+
+        let attributes: Vec<SpecAttribute> = attributes.into_iter().map(|(a, _)| a).collect();
+        self.expanded
+            .insert(template.identifier.clone(), attributes.clone());
+        attributes
+    }
+}
+
+/// Replaces the attributes of templates by their expanded attributes: those of the
+/// template they extend, then those of their groups (in order) and their own attributes.
+/// Attributes with the same identifier must be defined the same way.
+fn expand_templates(templates: &mut Vec<SpecTemplate>, groups: &[SpecGroup]) -> Vec<SpecError> {
+    let mut errors = vec![];
+    let mut group_map = HashMap::new();
+    for group in groups {
+        if group_map.insert(group.name.clone(), group).is_some() {
+            errors.push(SpecError::new(
+                group.line,
+                format!("duplicate attribute group {:?}!", group.name),
+            ));
+        }
+    }
+    let mut expansion = Expansion {
+        templates,
+        groups: group_map,
+        expanded: HashMap::new(),
+        errors,
+    };
+    let expanded: Vec<Vec<SpecAttribute>> = templates
+        .iter()
+        .map(|template| expansion.expand(template, &mut vec![]))
+        .collect();
+    let errors = expansion.errors;
+    for (template, attributes) in templates.iter_mut().zip(expanded) {
+        template.attributes = attributes;
+    }
+    errors
+}
+
+/// Reads a list of templates from YAML source, collecting all errors found.
+///
+/// The list may also contain attribute groups (`SpecGroup`), which are expanded into
+/// the templates including them like the attributes of templates they extend.
+/// Templates which could not be read are left out of the result.
+pub fn load_spec(source: &str) -> (Vec<SpecTemplate>, Vec<SpecError>) {
+    let mut builder = NodeBuilder::default();
+    if let Err(error) = Parser::new(source.chars()).load(&mut builder, false) {
+        #[allow(deprecated)]
+        let message = std::error::Error::description(&error).to_string();
+        return (vec![], vec![SpecError::new(error.marker().line(), message)]);
+    }
+
+    let root = match builder.root {
+        Some(root) => root,
+        None => return (vec![], vec![]),
+    };
+    let items = match root.kind {
+        NodeKind::Sequence(ref items) => items,
+        _ => {
+            let message = "the specification must be a list of templates!";
+            return (vec![], vec![SpecError::new(root.line, message)]);
+        }
+    };
+
+    let mut templates = vec![];
+    let mut groups = vec![];
+    let mut errors = vec![];
+    for item in items {
+        // attribute groups are list items with a `group` key.
+        if item.get("group").is_some() {
+            match load_group(item) {
+                Ok(group) => groups.push(group),
+                Err(mut e) => errors.append(&mut e),
+            }
+            continue;
+        }
+        match load_template(item) {
+            Ok(template) => templates.push(template),
+            Err(mut e) => errors.append(&mut e),
+        }
+    }
+    errors.append(&mut expand_templates(&mut templates, &groups));
+    (templates, errors)
+}
@@ -0,0 +1,270 @@
+//! Predicate references of the specification, like `no_templates`,
+//! `max_length(200)` or `crate::preds::only_inline`.
+//!
+//! Calls of the predicate library are checked when the reference is parsed.
+//! Other calls pass their literal arguments on, e.g. `min_items(3)`
+//! calls `min_items(root, 3)`.
+
+use quote::quote;
+use regex::Regex;
+use syn::{Expr, Lit, Path};
+
+/// Element kinds accepted by `only_kinds`, see `Element::get_variant_name`.
+const ELEMENT_KINDS: [&str; 18] = [
+    "Document",
+    "Heading",
+    "Text",
+    "Formatted",
+    "Paragraph",
+    "Template",
+    "TemplateArgument",
+    "InternalReference",
+    "ExternalReference",
+    "List",
+    "ListItem",
+    "Table",
+    "TableRow",
+    "TableCell",
+    "Comment",
+    "HtmlTag",
+    "Gallery",
+    "Error",
+];
+
+/// A reference to a predicate function, possibly with arguments.
+pub enum PredicateRef {
+    Path(Path),
+    Call(Path, Vec<PredicateArg>),
+}
+
+pub enum PredicateArg {
+    Lit(Lit),
+    Predicate(PredicateRef),
+}
+
+/// Parameters of the predicates of the library.
+pub enum Params {
+    Nothing,
+    Integer,
+    Pattern,
+    Kinds,
+    Predicates,
+    Negation,
+}
+
+/// The parameters of `path` if it names a predicate of the library.
+pub fn library_params(path: &Path) -> Option<Params> {
+    if path.leading_colon.is_some() || path.segments.len() != 1 {
+        return None;
+    }
+    let params = match path.segments[0].ident.to_string().as_str() {
+        "only_text" | "no_templates" | "no_block_elements" | "no_headings" => Params::Nothing,
+        "max_length" => Params::Integer,
+        "matches" => Params::Pattern,
+        "only_kinds" => Params::Kinds,
+        "all_of" | "any_of" => Params::Predicates,
+        "not" => Params::Negation,
+        _ => return None,
+    };
+    Some(params)
+}
+
+/// The path as written in the spec, without whitespace.
+pub fn path_name(path: &Path) -> String {
+    quote!(#path).to_string().replace(' ', "")
+}
+
+/// Checks the arguments of a call of a library predicate.
+fn check_call(path: &Path, args: &[PredicateArg]) -> Result<(), String> {
+    let name = path_name(path);
+    let params = match library_params(path) {
+        Some(params) => params,
+        None => return Ok(()),
+    };
+    match params {
+        Params::Nothing => Err(format!("`{}` takes no arguments!", name)),
+        Params::Integer => match args {
+            [PredicateArg::Lit(Lit::Int(_))] => Ok(()),
+            _ => Err(format!(
+                "`{}` takes one integer, like `{}(100)`!",
+                name, name
+            )),
+        },
+        Params::Pattern => match args {
+            [arg] if arg.string().is_some() => {
+                let pattern = arg.string().unwrap_or_default();
+                match Regex::new(&format!("^(?:{})$", pattern)) {
+                    Ok(_) => Ok(()),
+                    Err(error) => {
+                        // the last line of regex errors describes the problem.
+                        let message = error.to_string();
+                        let cause = message.lines().last().unwrap_or_default();
+                        Err(format!("invalid pattern {:?}: {}", pattern, cause))
+                    }
+                }
+            }
+            _ => Err(format!(
+                "`{}` takes one string, a regular expression!",
+                name
+            )),
+        },
+        Params::Kinds => {
+            if args.is_empty() {
+                return Err(format!("`{}` needs at least one element kind!", name));
+            }
+            for arg in args {
+                match arg.string() {
+                    Some(ref kind)
+                        if ELEMENT_KINDS.iter().any(|k| k.eq_ignore_ascii_case(kind)) => {}
+                    Some(kind) => return Err(format!("unknown element kind {:?}!", kind)),
+                    None => return Err(format!("`{}` only takes strings!", name)),
+                }
+            }
+            Ok(())
+        }
+        Params::Predicates => {
+            if args.is_empty() || !args.iter().all(|arg| arg.predicate().is_some()) {
+                return Err(format!("`{}` takes one or more predicates!", name));
+            }
+            Ok(())
+        }
+        Params::Negation => match args {
+            [ref pred] | [ref pred, PredicateArg::Lit(Lit::Str(_))]
+                if pred.predicate().is_some() =>
+            {
+                Ok(())
+            }
+            _ => Err(format!(
+                "`{}` takes a predicate and optionally a string describing the failure!",
+                name
+            )),
+        },
+    }
+}
+
+fn predicate_ref(expr: &Expr) -> Result<PredicateRef, String> {
+    match *expr {
+        Expr::Path(ref path) if path.qself.is_none() => {
+            if let Some(Params::Nothing) | None = library_params(&path.path) {
+                return Ok(PredicateRef::Path(path.path.clone()));
+            }
+            let name = path_name(&path.path);
+            Err(format!("`{}` needs arguments, like `{}(...)`!", name, name))
+        }
+        Expr::Call(ref call) => {
+            let path = match *call.func {
+                Expr::Path(ref path) if path.qself.is_none() => path.path.clone(),
+                _ => return Err("only functions given by a path can be called!".into()),
+            };
+            let mut args = vec![];
+            for arg in &call.args {
+                args.push(match *arg {
+                    Expr::Lit(ref lit) => PredicateArg::Lit(lit.lit.clone()),
+                    _ => PredicateArg::Predicate(predicate_ref(arg)?),
+                });
+            }
+            check_call(&path, &args)?;
+            Ok(PredicateRef::Call(path, args))
+        }
+        _ => Err("predicate arguments must be literals or predicates!".into()),
+    }
+}
+
+/// Reads a predicate reference from the spec.
+pub fn parse_predicate(source: &str) -> Result<PredicateRef, String> {
+    match syn::parse_str::<Expr>(source) {
+        Ok(expr) => predicate_ref(&expr),
+        Err(_) => Err(format!(
+            "predicate {:?} is neither a path nor a call!",
+            source
+        )),
+    }
+}
+
+impl PredicateRef {
+    /// Whether this predicate has arguments.
+    pub fn is_call(&self) -> bool {
+        match *self {
+            PredicateRef::Call(..) => true,
+            PredicateRef::Path(_) => false,
+        }
+    }
+
+    /// The path of the predicate function.
+    pub fn path(&self) -> &Path {
+        match *self {
+            PredicateRef::Path(ref path) | PredicateRef::Call(ref path, _) => path,
+        }
+    }
+
+    /// The arguments of a call, empty for paths.
+    pub fn args(&self) -> &[PredicateArg] {
+        match *self {
+            PredicateRef::Path(_) => &[],
+            PredicateRef::Call(_, ref args) => args,
+        }
+    }
+}
+
+impl PredicateArg {
+    /// The value of an integer literal.
+    pub fn integer(&self) -> Option<u64> {
+        match *self {
+            PredicateArg::Lit(Lit::Int(ref lit)) => Some(lit.value()),
+            _ => None,
+        }
+    }
+
+    /// The value of a float literal.
+    pub fn float(&self) -> Option<f64> {
+        match *self {
+            PredicateArg::Lit(Lit::Float(ref lit)) => Some(lit.value()),
+            _ => None,
+        }
+    }
+
+    /// The value of a string literal.
+    pub fn string(&self) -> Option<String> {
+        match *self {
+            PredicateArg::Lit(Lit::Str(ref lit)) => Some(lit.value()),
+            _ => None,
+        }
+    }
+
+    /// The value of a boolean literal.
+    pub fn boolean(&self) -> Option<bool> {
+        match *self {
+            PredicateArg::Lit(Lit::Bool(ref lit)) => Some(lit.value),
+            _ => None,
+        }
+    }
+
+    /// The predicate given as argument.
+    pub fn predicate(&self) -> Option<&PredicateRef> {
+        match *self {
+            PredicateArg::Predicate(ref predicate) => Some(predicate),
+            PredicateArg::Lit(_) => None,
+        }
+    }
+
+    /// The argument as written in the spec, without whitespace.
+    pub fn describe(&self) -> String {
+        match *self {
+            PredicateArg::Lit(ref lit) => quote!(#lit).to_string(),
+            PredicateArg::Predicate(PredicateRef::Path(ref path)) => path_name(path),
+            PredicateArg::Predicate(PredicateRef::Call(ref path, ref args)) => {
+                let args: Vec<String> = args.iter().map(PredicateArg::describe).collect();
+                format!("{}({})", path_name(path), args.join(", "))
+            }
+        }
+    }
+}
+
+/// The failure of `not(predicate, cause)`, a description of the predicate if `cause` is missing.
+pub fn negation_cause(args: &[PredicateArg]) -> String {
+    match args {
+        [_, cause] => cause.string().unwrap_or_default(),
+        [predicate] => format!("must not satisfy {}!", predicate.describe()),
+        _ => String::new(),
+    }
+}
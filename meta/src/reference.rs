@@ -0,0 +1,234 @@
+//! Reference documentation of a specification for template authors,
+//! rendered as Markdown or HTML.
+
+use crate::spec::{SpecAttribute, SpecFormat, SpecPriority, SpecTemplate, SpecType};
+
+fn format_name(format: SpecFormat) -> &'static str {
+    match format {
+        SpecFormat::Block => "block",
+        SpecFormat::Box => "box",
+        SpecFormat::Inline => "inline",
+    }
+}
+
+/// Names and position an attribute can be given by, e.g. `title`, `1`.
+fn attribute_names(attribute: &SpecAttribute) -> Vec<String> {
+    let mut names = attribute.names.clone();
+    if let Some(position) = attribute.position {
+        names.push(position.to_string());
+    }
+    names
+}
+
+/// Deprecated names of a template or attribute, if there are any.
+fn deprecation_note(deprecated_names: &[String], code: fn(&[String]) -> String) -> String {
+    if deprecated_names.is_empty() {
+        return String::new();
+    }
+    format!(" (deprecated: {})", code(deprecated_names))
+}
+
+/// Priority of an attribute, with its default or number of values.
+fn priority_text(attribute: &SpecAttribute) -> String {
+    let mut text = match attribute.priority {
+        SpecPriority::Required => "required".to_string(),
+        SpecPriority::Optional => "optional".to_string(),
+    };
+    if let Some(ref default) = attribute.default {
+        text.push_str(&format!(", default `{}`", default.trim()));
+    }
+    if attribute.repeated {
+        match attribute.max_count {
+            Some(max) => text.push_str(&format!(", {} to {} values", attribute.min_count(), max)),
+            None => text.push_str(&format!(", at least {} values", attribute.min_count())),
+        }
+    }
+    text
+}
+
+/// A value for an attribute in the example invocation.
+fn example_value(attribute: &SpecAttribute) -> String {
+    if let Some(ref default) = attribute.default {
+        return default.trim().to_string();
+    }
+    match attribute.value_type {
+        SpecType::Elements | SpecType::Text => "...".into(),
+        SpecType::Integer => "100".into(),
+        SpecType::Boolean => "yes".into(),
+        SpecType::Enum => attribute.values.first().cloned().unwrap_or_default(),
+        SpecType::Formula => "<math>x^2</math>".into(),
+        SpecType::File => "Example.svg".into(),
+    }
+}
+
+/// An invocation of a template using all of its attributes.
+///
+/// Repeated attributes get two values, unless they allow only one.
+/// Of each `one_of` group only the first attribute is used, attributes conflicting
+/// with earlier ones (or requiring left out ones) are left out.
+/// Arguments of block and box templates are written on lines of their own.
+pub fn example_invocation(template: &SpecTemplate) -> String {
+    let mut arguments = vec![];
+    let mut positional = vec![];
+    let mut used: Vec<&SpecAttribute> = vec![];
+    for attribute in &template.attributes {
+        let id = &attribute.identifier;
+        let alternative = template
+            .one_of
+            .iter()
+            .any(|group| group.contains(id) && group.first() != Some(id));
+        let conflicting = used.iter().any(|u| {
+            u.conflicts_with.contains(id) || attribute.conflicts_with.contains(&u.identifier)
+        });
+        let unmet = attribute
+            .requires
+            .iter()
+            .any(|r| !used.iter().any(|u| u.identifier == *r));
+        if alternative || conflicting || unmet {
+            continue;
+        }
+        used.push(attribute);
+        let value = example_value(attribute);
+        let name = match attribute.names.first() {
+            Some(name) => name.trim().to_lowercase(),
+            None => {
+                positional.push((attribute.position.unwrap_or_default(), value));
+                continue;
+            }
+        };
+        if attribute.repeated {
+            let count = attribute.max_count.unwrap_or(2).min(2);
+            for number in 1..=count {
+                arguments.push(format!("{}{}={}", name, number, value));
+            }
+        } else {
+            arguments.push(format!("{}={}", name, value));
+        }
+    }
+    // unnamed arguments are bound in order, others are given by their position.
+    positional.sort_by_key(|(position, _)| *position);
+    let mut bound = 0;
+    let mut unnamed = vec![];
+    for (position, value) in positional {
+        if position == bound + 1 {
+            bound += 1;
+            unnamed.push(value);
+        } else {
+            unnamed.push(format!("{}={}", position, value));
+        }
+    }
+    unnamed.append(&mut arguments);
+    let arguments = unnamed;
+
+    let name = template.names.first().map(|n| n.trim().to_lowercase());
+    let separator = match template.format {
+        SpecFormat::Inline => "|",
+        SpecFormat::Block | SpecFormat::Box => "\n|",
+    };
+    let mut invocation = format!("{{{{{}", name.unwrap_or_default());
+    for argument in arguments {
+        invocation.push_str(separator);
+        invocation.push_str(&argument);
+    }
+    if template.format != SpecFormat::Inline {
+        invocation.push('\n');
+    }
+    invocation.push_str("}}");
+    invocation
+}
+
+/// Makes text fit into a cell of a Markdown table.
+fn markdown_cell(text: &str) -> String {
+    text.trim().replace('|', "\\|").replace('\n', " ")
+}
+
+fn markdown_code(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|i| format!("`{}`", i.trim())).collect();
+    items.join(", ")
+}
+
+/// Renders an authoring guide for templates as Markdown.
+pub fn reference_markdown(templates: &[SpecTemplate]) -> String {
+    let mut result = String::from("# Templates\n");
+    for template in templates {
+        result.push_str(&format!("\n## {}\n\n", template.identifier));
+        result.push_str(&format!("{}\n\n", template.description.trim()));
+        result.push_str(&format!("Format: {}\n\n", format_name(template.format)));
+        result.push_str(&format!(
+            "Names: {}{}\n\n",
+            markdown_code(&template.names),
+            deprecation_note(&template.deprecated_names, markdown_code)
+        ));
+        result.push_str("| Attribute | Names | Priority | Predicate | Description |\n");
+        result.push_str("|-----------|-------|----------|-----------|-------------|\n");
+        for attribute in &template.attributes {
+            result.push_str(&format!(
+                "| `{}` | {}{} | {} | `{}` | {} |\n",
+                attribute.identifier,
+                markdown_cell(&markdown_code(&attribute_names(attribute))),
+                deprecation_note(&attribute.deprecated_names, markdown_code),
+                markdown_cell(&priority_text(attribute)),
+                markdown_cell(&attribute.predicate),
+                markdown_cell(&attribute.description),
+            ));
+        }
+        result.push_str(&format!(
+            "\nExample:\n\n```\n{}\n```\n",
+            example_invocation(template)
+        ));
+    }
+    result
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_code(items: &[String]) -> String {
+    let items: Vec<String> = items
+        .iter()
+        .map(|i| format!("<code>{}</code>", escape_html(i.trim())))
+        .collect();
+    items.join(", ")
+}
+
+/// Renders an authoring guide for templates as HTML, without a surrounding document.
+pub fn reference_html(templates: &[SpecTemplate]) -> String {
+    let mut result = String::from("<h1>Templates</h1>\n");
+    for template in templates {
+        let id = escape_html(&template.identifier);
+        result.push_str(&format!("<section id=\"{}\">\n<h2>{}</h2>\n", id, id));
+        result.push_str(&format!(
+            "<p>{}</p>\n",
+            escape_html(template.description.trim())
+        ));
+        result.push_str(&format!(
+            "<p>Format: {}</p>\n<p>Names: {}{}</p>\n",
+            format_name(template.format),
+            html_code(&template.names),
+            deprecation_note(&template.deprecated_names, html_code)
+        ));
+        result.push_str("<table>\n<tr><th>Attribute</th><th>Names</th><th>Priority</th>");
+        result.push_str("<th>Predicate</th><th>Description</th></tr>\n");
+        for attribute in &template.attributes {
+            result.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}{}</td><td>{}</td>\
+                 <td><code>{}</code></td><td>{}</td></tr>\n",
+                escape_html(&attribute.identifier),
+                html_code(&attribute_names(attribute)),
+                deprecation_note(&attribute.deprecated_names, html_code),
+                escape_html(&priority_text(attribute).replace('`', "")),
+                escape_html(attribute.predicate.trim()),
+                escape_html(attribute.description.trim()),
+            ));
+        }
+        result.push_str(&format!(
+            "</table>\n<p>Example:</p>\n<pre>{}</pre>\n</section>\n",
+            escape_html(&example_invocation(template))
+        ));
+    }
+    result
+}
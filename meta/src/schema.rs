@@ -0,0 +1,175 @@
+//! JSON Schema of the serialized template structs generated by `template_spec!`.
+//!
+//! The schema describes `KnownTemplate`, which serializes as an object with the
+//! template identifier as its only key, e.g. `{"Example": {"identifier": "Example", ...}}`.
+
+use crate::spec::{SpecAttribute, SpecFormat, SpecPriority, SpecTemplate, SpecType};
+use serde_json::{json, Map, Value};
+
+fn reference(definition: &str) -> Value {
+    json!({ "$ref": format!("#/definitions/{}", definition) })
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// Schema of a single value of an attribute.
+fn value_schema(attribute: &SpecAttribute) -> Value {
+    match attribute.value_type {
+        SpecType::Elements => reference("Elements"),
+        SpecType::Text | SpecType::Formula | SpecType::File => json!({ "type": "string" }),
+        SpecType::Integer => json!({ "type": "integer" }),
+        SpecType::Boolean => json!({ "type": "boolean" }),
+        SpecType::Enum => {
+            let values: Vec<String> = attribute
+                .values
+                .iter()
+                .map(|v| v.trim().to_lowercase())
+                .collect();
+            json!({ "type": "string", "enum": values })
+        }
+    }
+}
+
+/// Schema of the struct field of an attribute.
+fn attribute_schema(attribute: &SpecAttribute) -> Value {
+    let schema = if attribute.repeated {
+        let mut schema = array_of(value_schema(attribute));
+        schema["minItems"] = json!(attribute.min_count());
+        if let Some(max) = attribute.max_count {
+            schema["maxItems"] = json!(max);
+        }
+        schema
+    } else if attribute.priority == SpecPriority::Optional && attribute.default.is_none() {
+        json!({ "oneOf": [value_schema(attribute), { "type": "null" }] })
+    } else {
+        value_schema(attribute)
+    };
+    // keywords next to `$ref` are ignored.
+    json!({
+        "description": attribute.description.trim(),
+        "allOf": [schema],
+    })
+}
+
+fn format_name(format: SpecFormat) -> &'static str {
+    match format {
+        SpecFormat::Block => "Block",
+        SpecFormat::Box => "Box",
+        SpecFormat::Inline => "Inline",
+    }
+}
+
+fn template_schema(template: &SpecTemplate) -> Value {
+    let names: Vec<String> = template
+        .names
+        .iter()
+        .map(|n| n.trim().to_lowercase())
+        .collect();
+    let mut properties = Map::new();
+    properties.insert("identifier".into(), json!({ "const": template.identifier }));
+    properties.insert("names".into(), json!({ "const": names }));
+    properties.insert(
+        "format".into(),
+        json!({ "const": format_name(template.format) }),
+    );
+    properties.insert("description".into(), json!({ "type": "string" }));
+    properties.insert("present".into(), array_of(reference("Attribute")));
+    properties.insert("unknown".into(), array_of(reference("UnknownArgument")));
+    for attribute in &template.attributes {
+        properties.insert(attribute.identifier.clone(), attribute_schema(attribute));
+    }
+    let required: Vec<&String> = properties.keys().collect();
+    json!({
+        "type": "object",
+        "description": template.description.trim(),
+        "required": required,
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// The JSON Schema (draft 7) of the serialized `KnownTemplate` for a specification.
+pub fn json_schema(templates: &[SpecTemplate]) -> Value {
+    let mut definitions = Map::new();
+    let mut variants = vec![];
+    for template in templates {
+        let id = &template.identifier;
+        definitions.insert(id.clone(), template_schema(template));
+        variants.push(json!({
+            "type": "object",
+            "required": [id],
+            "properties": { id.as_str(): reference(id) },
+            "additionalProperties": false,
+        }));
+    }
+
+    definitions.insert(
+        "Attribute".into(),
+        json!({
+            "description": "A template attribute given in the source.",
+            "type": "object",
+            "required": ["name", "priority", "value"],
+            "properties": {
+                "name": { "type": "string" },
+                "priority": { "enum": ["Required", "Optional"] },
+                "value": reference("Elements"),
+            },
+            "additionalProperties": false,
+        }),
+    );
+    definitions.insert(
+        "UnknownArgument".into(),
+        json!({
+            "description": "A template argument which does not belong to any attribute.",
+            "type": "object",
+            "required": ["name", "suggestion", "value", "position"],
+            "properties": {
+                "name": { "type": "string" },
+                "suggestion": { "type": ["string", "null"] },
+                "value": reference("Elements"),
+                "position": reference("Span"),
+            },
+            "additionalProperties": false,
+        }),
+    );
+    definitions.insert("Elements".into(), array_of(reference("Element")));
+    definitions.insert(
+        "Element".into(),
+        json!({
+            "description": "An element of the syntax tree of mediawiki_parser.",
+            "type": "object",
+            "required": ["type"],
+            "properties": { "type": { "type": "string" } },
+        }),
+    );
+    definitions.insert(
+        "Span".into(),
+        json!({
+            "type": "object",
+            "properties": {
+                "start": reference("Position"),
+                "end": reference("Position"),
+            },
+        }),
+    );
+    definitions.insert(
+        "Position".into(),
+        json!({
+            "type": "object",
+            "properties": {
+                "offset": { "type": "integer" },
+                "line": { "type": "integer" },
+                "col": { "type": "integer" },
+            },
+        }),
+    );
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "KnownTemplate",
+        "oneOf": variants,
+        "definitions": definitions,
+    })
+}
@@ -0,0 +1,206 @@
+use serde::de::{Deserialize as _, Deserializer, Error};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Deserializes a plain value written with or without quotes, like `200` or `"200"`.
+fn plain_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(value) => Ok(Some(value)),
+        Value::Number(value) => Ok(Some(value.to_string())),
+        Value::Bool(value) => Ok(Some(value.to_string())),
+        other => Err(D::Error::custom(format!(
+            "invalid type: {}, expected a plain value",
+            other
+        ))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecFormat {
+    Block,
+    Box,
+    Inline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecPriority {
+    Required,
+    Optional,
+}
+
+/// Where in a document a template has to be placed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecPosition {
+    First,
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecType {
+    #[default]
+    Elements,
+    Text,
+    Integer,
+    Boolean,
+    Enum,
+    Formula,
+    File,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecTemplate {
+    #[serde(rename = "id")]
+    pub identifier: String,
+    pub description: String,
+    pub names: Vec<String>,
+    /// Old names, which are accepted but should be replaced by the first of `names`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deprecated_names: Vec<String>,
+    pub format: SpecFormat,
+    /// Identifier of a template whose attributes this template inherits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Names of attribute groups whose attributes this template includes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Identifiers of templates this template may only appear in, at any depth.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_in: Vec<String>,
+    /// Identifiers of templates this template may not appear in, at any depth.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden_in: Vec<String>,
+    /// Identifiers of the only templates allowed directly inside this template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_children: Option<Vec<String>>,
+    /// Groups of attribute identifiers, exactly one attribute of each group must be given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub one_of: Vec<Vec<String>>,
+    /// Maximum number of occurrences of this template in a document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_per_document: Option<usize>,
+    /// Wether this template must be at the start or the end of a document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<SpecPosition>,
+    pub attributes: Vec<SpecAttribute>,
+    /// Line of this template in the spec file.
+    #[serde(skip)]
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecAttribute {
+    #[serde(rename = "id")]
+    pub identifier: String,
+    pub description: String,
+    pub names: Vec<String>,
+    /// Old names, which are accepted but should be replaced by the first of `names`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deprecated_names: Vec<String>,
+    pub priority: SpecPriority,
+    pub predicate: String,
+    /// Value of an optional attribute if it is missing. Wikitext for `elements`
+    /// attributes, a plain value otherwise.
+    #[serde(
+        default,
+        deserialize_with = "plain_value",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default: Option<String>,
+    /// Position (starting at 1) of an unnamed argument filling this attribute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    /// Repeated attributes collect all arguments named by one of
+    /// `names` followed by a number, like `item1` to `itemN`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub repeated: bool,
+    /// Minimum number of values of a repeated attribute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_count: Option<usize>,
+    /// Maximum number of values of a repeated attribute.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_count: Option<usize>,
+    #[serde(rename = "type", default)]
+    pub value_type: SpecType,
+    /// Allowed values of an `enum` attribute.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    /// Identifiers of attributes which must be given if this attribute is given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+    /// Identifiers of attributes which cannot be given together with this attribute.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts_with: Vec<String>,
+    /// Line of this attribute in the spec file.
+    #[serde(skip)]
+    pub line: usize,
+}
+
+/// Attributes shared by several templates, included by their `groups`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpecGroup {
+    #[serde(rename = "group")]
+    pub name: String,
+    pub attributes: Vec<SpecAttribute>,
+    /// Line of this group in the spec file.
+    #[serde(skip)]
+    pub line: usize,
+}
+
+/// Names and deprecated names, trimmed and lowercase.
+fn accepted_names(names: &[String], deprecated_names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .chain(deprecated_names)
+        .map(|n| n.trim().to_lowercase())
+        .collect()
+}
+
+impl SpecTemplate {
+    /// All names this template is recognized by, including deprecated ones.
+    pub fn accepted_names(&self) -> Vec<String> {
+        accepted_names(&self.names, &self.deprecated_names)
+    }
+}
+
+impl SpecAttribute {
+    /// All names this attribute is recognized by, including deprecated ones.
+    pub fn accepted_names(&self) -> Vec<String> {
+        accepted_names(&self.names, &self.deprecated_names)
+    }
+
+    /// Minimum number of values of a repeated attribute.
+    pub fn min_count(&self) -> usize {
+        match (self.min_count, self.priority) {
+            (Some(min), _) => min,
+            (None, SpecPriority::Required) => 1,
+            (None, SpecPriority::Optional) => 0,
+        }
+    }
+}
+
+/// Writes templates in the YAML format `load_spec` reads.
+///
+/// Attributes are written as expanded by `load_spec`, so `extends` and `groups` are left out.
+pub fn write_spec(templates: &[SpecTemplate]) -> String {
+    let templates: Vec<SpecTemplate> = templates
+        .iter()
+        .map(|template| SpecTemplate {
+            extends: None,
+            groups: vec![],
+            ..template.clone()
+        })
+        .collect();
+    serde_yaml::to_string(&templates).expect("specs should be serializable!")
+}
@@ -0,0 +1,459 @@
+use crate::reference::example_invocation;
+use crate::{check_spec, load_spec, sort_errors, write_spec, SpecError};
+
+/// Loads and checks a spec like `template_spec!` does, with every predicate accepted.
+/// Errors are given as `line: message`.
+fn spec_errors(yaml: &str) -> Vec<String> {
+    let (templates, mut errors) = load_spec(yaml);
+    errors.append(&mut check_spec(&templates, &|_| Ok(())));
+    sort_errors(&mut errors);
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.line, error.message))
+        .collect()
+}
+
+#[test]
+fn valid_spec() {
+    let spec = "
+- id: Example
+  names: [example]
+  description: An example.
+  format: box
+  attributes:
+    - id: title
+      names: [title]
+      description: A title.
+      priority: optional
+      predicate: p
+";
+    assert_eq!(spec_errors(spec), Vec::<String>::new());
+}
+
+#[test]
+fn unknown_fields() {
+    let spec = "
+- id: Example
+  names: [example]
+  description: An example.
+  format: box
+  colour: red
+  attributes:
+    - id: title
+      names: [title]
+      description: A title.
+      priority: optional
+      predicat: p
+";
+    let errors = spec_errors(spec);
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("6: unknown field `colour`, expected one of `id`"));
+    assert!(errors[1].starts_with("12: unknown field `predicat`, expected one of `id`"));
+}
+
+#[test]
+fn invalid_values() {
+    let spec = "
+- id: Example
+  names: [example]
+  description: An example.
+  format: paragraph
+  attributes: []
+
+- id: Other
+  names: [other]
+  description: Another template.
+  format: inline
+  attributes:
+    - id: title
+      names: [title]
+      description: A title.
+      priority: sometimes
+      predicate: p
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec![
+            "5: unknown variant `paragraph`, expected one of `block`, `box`, `inline`",
+            "16: unknown variant `sometimes`, expected `required` or `optional`",
+        ]
+    );
+}
+
+#[test]
+fn duplicates() {
+    let spec = "
+- id: Example
+  names: [example]
+  description: An example.
+  format: box
+  attributes:
+    - id: title
+      names: [title]
+      description: A title.
+      priority: optional
+      predicate: p
+
+    - id: title
+      names: [heading]
+      description: Another title.
+      priority: optional
+      predicate: p
+
+- id: Example
+  names: [Example]
+  description: The same example.
+  format: box
+  attributes: []
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec![
+            "13: duplicate attribute identifier \"title\", first defined at line 7!",
+            "19: duplicate template identifier \"Example\", first defined at line 2!",
+            "19: duplicate template name \"example\", first defined at line 2!",
+        ]
+    );
+}
+
+#[test]
+fn generated_names() {
+    let spec = "
+- id: Figure
+  names: [figure]
+  description: A figure.
+  format: block
+  attributes: []
+
+- id: FigureBuilder
+  names: [figure builder]
+  description: Builds figures.
+  format: block
+  attributes: []
+
+- id: Template
+  names: [template]
+  description: A template.
+  format: inline
+  attributes: []
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec![
+            "8: \"FigureBuilder\" is the name of the builder of \"Figure\"!",
+            "14: \"Template\" is reserved and cannot be used as template identifier!",
+        ]
+    );
+}
+
+#[test]
+fn collects_all_errors() {
+    let spec = "
+- id: example
+  names: []
+  description: An example.
+  format: box
+  attributes:
+    - id: Title
+      names: [title]
+      description: A title.
+      priority: required
+      predicate: p
+      default: foo
+      position: 0
+
+    - id: my title
+      names: [heading]
+      description: Another title.
+      priority: optional
+      predicate: p
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec![
+            "2: first character of identifier \"example\" should be uppercase!",
+            "2: \"example\": templates must have at least one name!",
+            "7: \"Title\": attribute identifiers should be lowercase!",
+            "7: \"Title\": argument positions start at 1!",
+            "7: \"Title\": required attributes cannot have a default!",
+            "15: \"my title\" is not a valid identifier!",
+        ]
+    );
+}
+
+#[test]
+fn placement_rules() {
+    let spec = "
+- id: Box
+  names: [box]
+  description: A box.
+  format: box
+  allowed_in: [Missing]
+  forbidden_in: [Box]
+  allowed_children: [Box]
+  attributes: []
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec!["2: \"Box\": unknown template \"Missing\" in allowed_in!"]
+    );
+}
+
+#[test]
+fn attribute_constraints() {
+    let spec = "
+- id: Box
+  names: [box]
+  description: A box.
+  format: box
+  one_of: [[a]]
+  attributes:
+    - id: a
+      names: [a]
+      description: A.
+      priority: required
+      predicate: p
+      requires: [b]
+      conflicts_with: [a]
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec![
+            "2: \"Box\": one_of groups need at least two attributes!",
+            "2: \"Box\": \"a\" in one_of must be optional!",
+            "8: \"a\": unknown attribute \"b\" in requires!",
+            "8: \"a\": conflicts_with cannot refer to the attribute itself!",
+        ]
+    );
+}
+
+#[test]
+fn document_occurrences() {
+    let spec = "
+- id: Header
+  names: [header]
+  description: A header.
+  format: block
+  position: first
+  max_per_document: 0
+  allowed_in: [Header]
+  attributes: []
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec![
+            "2: \"Header\": max_per_document must be at least 1!",
+            "2: \"Header\": templates with a position cannot have allowed_in!",
+        ]
+    );
+}
+
+const GROUPS: &str = "
+- group: titled
+  attributes:
+    - id: title
+      names: [title]
+      description: A title.
+      priority: optional
+      predicate: p
+
+- id: Theorem
+  names: [theorem]
+  description: A theorem.
+  format: box
+  groups: [titled]
+  attributes:
+    - id: statement
+      names: [statement]
+      description: The statement.
+      priority: required
+      predicate: p
+
+- id: Lemma
+  names: [lemma]
+  description: A lemma.
+  format: box
+  extends: Theorem
+  groups: [titled]
+  attributes:
+    - id: used_for
+      names: [for]
+      description: The theorem this lemma is used for.
+      priority: optional
+      predicate: p
+";
+
+#[test]
+fn attribute_groups_and_extends() {
+    assert_eq!(spec_errors(GROUPS), Vec::<String>::new());
+    let (templates, _) = load_spec(GROUPS);
+    let ids: Vec<&str> = templates[1]
+        .attributes
+        .iter()
+        .map(|a| a.identifier.as_str())
+        .collect();
+    assert_eq!(ids, vec!["title", "statement", "used_for"]);
+    assert_eq!(templates[1].attributes[0].line, 4);
+    let (written, errors) = load_spec(&write_spec(&templates));
+    assert_eq!(errors, vec![]);
+    assert_eq!(written[1].attributes.len(), 3);
+}
+
+#[test]
+fn attribute_group_errors() {
+    let spec = GROUPS.replace(
+        "    - id: used_for\n      names: [for]",
+        "    - id: statement\n      names: [for]",
+    );
+    let spec = format!(
+        "{}\n- id: Cycle\n  names: [cycle]\n  description: A cycle.\n  format: box\n  \
+         extends: Cycle\n  groups: [missing]\n  attributes: []\n",
+        spec
+    );
+    assert_eq!(
+        spec_errors(&spec),
+        vec![
+            "29: \"Lemma\": attribute \"statement\" from template \"Lemma\" conflicts \
+             with the one from template \"Theorem\"!",
+            "35: \"Cycle\": extends itself!",
+            "35: \"Cycle\": unknown attribute group \"missing\"!",
+        ]
+    );
+}
+
+#[test]
+fn removes_duplicate_errors() {
+    let error = |line, message: &str| SpecError {
+        line,
+        message: message.into(),
+    };
+    let mut errors = vec![
+        error(3, "a"),
+        error(1, "b"),
+        error(3, "c"),
+        error(3, "a"),
+        error(1, "b"),
+    ];
+    sort_errors(&mut errors);
+    assert_eq!(errors, vec![error(1, "b"), error(3, "a"), error(3, "c")]);
+}
+
+#[test]
+fn plain_defaults() {
+    let spec = "
+- id: Figure
+  names: [figure]
+  description: A figure.
+  format: block
+  attributes:
+    - id: width
+      names: [width]
+      description: The width.
+      priority: optional
+      predicate: p
+      type: integer
+      default: 200
+
+    - id: framed
+      names: [framed]
+      description: Wether the figure has a frame.
+      priority: optional
+      predicate: p
+      type: boolean
+      default: true
+
+    - id: caption
+      names: [caption]
+      description: A caption.
+      priority: optional
+      predicate: p
+      default: [a, b]
+";
+    assert_eq!(
+        spec_errors(spec),
+        vec!["23: invalid type: [\"a\",\"b\"], expected a plain value"]
+    );
+    let (templates, _) = load_spec(&spec.replace("default: [a, b]", "default: 'a, b'"));
+    let defaults: Vec<Option<&str>> = templates[0]
+        .attributes
+        .iter()
+        .map(|a| a.default.as_deref())
+        .collect();
+    assert_eq!(defaults, vec![Some("200"), Some("true"), Some("a, b")]);
+}
+
+#[test]
+fn wikitext_defaults() {
+    let spec = "
+- id: Example
+  names: [example]
+  description: An example.
+  format: box
+  attributes:
+    - id: title
+      names: [title]
+      description: A title.
+      priority: optional
+      predicate: p
+      type: elements
+      default: \"''Example'' {{Icon}}\"
+
+    - id: hint
+      names: [hint]
+      description: A hint.
+      priority: optional
+      predicate: p
+      type: elements
+      default: \"tail}\"
+";
+    let errors = spec_errors(spec);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with(
+        "15: \"hint\": default \"tail}\" is not valid wikitext: \
+         ERROR in line 1 at column 5: Could not continue to parse"
+    ));
+    assert!(!errors[0].contains('\n'));
+}
+
+#[test]
+fn positional_examples() {
+    let spec = "
+- id: Range
+  names: [range]
+  description: A range.
+  format: inline
+  attributes:
+    - id: end
+      names: []
+      description: The end.
+      priority: required
+      predicate: p
+      position: 2
+
+    - id: start
+      names: []
+      description: The start.
+      priority: required
+      predicate: p
+      position: 1
+
+    - id: step
+      names: [step]
+      description: The step.
+      priority: optional
+      predicate: p
+
+    - id: unit
+      names: []
+      description: The unit.
+      priority: optional
+      predicate: p
+      position: 4
+";
+    let (templates, errors) = load_spec(spec);
+    assert!(errors.is_empty());
+    assert_eq!(
+        example_invocation(&templates[0]),
+        "{{range|...|...|4=...|step=...}}"
+    );
+}
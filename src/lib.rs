@@ -1,5 +1,13 @@
 //! This library provides common, Mathe-für-Nicht-Freaks specific code.
 
+// `TResult` of mediawiki_parser has a large error variant we cannot change.
+#![allow(clippy::result_large_err)]
+
+// the code generated by `template_spec!` refers to this crate by name, also in its tests.
+extern crate self as mwparser_utils;
+
+pub mod registry;
+pub mod template_data;
 pub mod transformations;
 mod util;
 
@@ -0,0 +1,581 @@
+//! Template specifications loaded at runtime.
+//!
+//! `SpecRegistry` reads the same YAML format as `template_spec!`, for tools
+//! checking wikis whose templates change without a rebuild.
+
+use crate::util::*;
+use mediawiki_parser::*;
+use mwparser_utils_meta::check::boolean_value;
+use mwparser_utils_meta::predicate::{
+    negation_cause, parse_predicate, path_name, PredicateArg, PredicateRef,
+};
+use mwparser_utils_meta::{check_spec, load_spec, reference_html, reference_markdown, sort_errors};
+pub use mwparser_utils_meta::{
+    SpecAttribute, SpecError, SpecFormat, SpecPosition, SpecPriority, SpecTemplate, SpecType,
+};
+use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A predicate checking attribute content at runtime, given the literal arguments
+/// of the reference in the spec, returning the cause of a failure.
+pub type RuntimePredicate =
+    dyn Fn(&[Element], &[PredicateLiteral]) -> Result<(), String> + Send + Sync;
+
+/// A literal argument of a predicate reference, like `3` in `min_items(3)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PredicateLiteral {
+    Integer(u64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+}
+
+/// A predicate reference of the spec, resolved when the spec is loaded.
+enum ResolvedPredicate {
+    OnlyText,
+    NoTemplates,
+    NoBlockElements,
+    NoHeadings,
+    MaxLength(usize),
+    Matches(String),
+    OnlyKinds(Vec<String>),
+    AllOf(Vec<ResolvedPredicate>),
+    AnyOf(Vec<ResolvedPredicate>),
+    Not(Box<ResolvedPredicate>, String),
+    /// A registered predicate with its arguments.
+    Registered(String, Vec<PredicateLiteral>),
+}
+
+/// The value of an attribute, converted according to its type.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum AttributeValue<'e> {
+    /// Content of `elements` attributes, owned for default values.
+    Elements(Cow<'e, [Element]>),
+    /// Value of `text`, `enum`, `formula` and `file` attributes.
+    Text(String),
+    Integer(i64),
+    Boolean(bool),
+    /// Values of a repeated attribute.
+    List(Vec<AttributeValue<'e>>),
+}
+
+/// A template parsed with a runtime specification.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DynamicTemplate<'e> {
+    pub identifier: String,
+    /// Values of present attributes and defaults, by attribute identifier.
+    pub attributes: HashMap<String, AttributeValue<'e>>,
+    /// Names of arguments which do not belong to any attribute,
+    /// with the closest attribute name if it looks like a typo.
+    pub unknown: Vec<(String, Option<String>)>,
+}
+
+/// Reason why a template does not fit a runtime specification.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateError {
+    /// Identifier of the template, if it is known.
+    pub template: Option<String>,
+    /// Identifier of the offending attribute, if any.
+    pub attribute: Option<String>,
+    pub message: String,
+    /// Position of the template.
+    pub position: Span,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}", self.message, self.position.start.line)
+    }
+}
+
+fn predicate_literal(arg: &PredicateArg) -> Option<PredicateLiteral> {
+    arg.integer()
+        .map(PredicateLiteral::Integer)
+        .or_else(|| arg.float().map(PredicateLiteral::Float))
+        .or_else(|| arg.string().map(PredicateLiteral::String))
+        .or_else(|| arg.boolean().map(PredicateLiteral::Boolean))
+}
+
+/// Resolves a predicate reference to the library or to registered `predicates`.
+fn resolve(
+    reference: &PredicateRef,
+    predicates: &HashMap<String, Box<RuntimePredicate>>,
+) -> Result<ResolvedPredicate, String> {
+    let name = path_name(reference.path());
+    let args = reference.args();
+    // arguments of library predicates are checked by `parse_predicate`.
+    let resolve_all = || -> Result<Vec<ResolvedPredicate>, String> {
+        args.iter()
+            .filter_map(PredicateArg::predicate)
+            .map(|predicate| resolve(predicate, predicates))
+            .collect()
+    };
+    let resolved = match name.as_str() {
+        "only_text" => ResolvedPredicate::OnlyText,
+        "no_templates" => ResolvedPredicate::NoTemplates,
+        "no_block_elements" => ResolvedPredicate::NoBlockElements,
+        "no_headings" => ResolvedPredicate::NoHeadings,
+        "max_length" => {
+            ResolvedPredicate::MaxLength(args[0].integer().unwrap_or_default() as usize)
+        }
+        "matches" => ResolvedPredicate::Matches(args[0].string().unwrap_or_default()),
+        "only_kinds" => {
+            ResolvedPredicate::OnlyKinds(args.iter().filter_map(PredicateArg::string).collect())
+        }
+        "all_of" => ResolvedPredicate::AllOf(resolve_all()?),
+        "any_of" => ResolvedPredicate::AnyOf(resolve_all()?),
+        "not" => {
+            let mut negated = resolve_all()?;
+            ResolvedPredicate::Not(Box::new(negated.remove(0)), negation_cause(args))
+        }
+        _ if predicates.contains_key(&name) => {
+            let mut literals = vec![];
+            for arg in args {
+                literals.push(predicate_literal(arg).ok_or_else(|| {
+                    format!(
+                        "`{}` takes only integers, floats, strings and booleans at runtime!",
+                        name
+                    )
+                })?);
+            }
+            ResolvedPredicate::Registered(name, literals)
+        }
+        _ => return Err(format!("unknown predicate {:?}!", name)),
+    };
+    Ok(resolved)
+}
+
+/// Template specifications and the predicates they refer to.
+///
+/// The predicate library of `template_spec!`, like `no_templates` or `max_length(200)`,
+/// is built in. Other predicates are registered by their path, like `min_items`
+/// or `crate::preds::only_inline`, and get the literal arguments of the reference,
+/// e.g. `[Integer(3)]` for `min_items(3)`.
+#[derive(Default)]
+pub struct SpecRegistry {
+    templates: Vec<SpecTemplate>,
+    predicates: HashMap<String, Box<RuntimePredicate>>,
+    /// Predicates of the attributes, by reference.
+    resolved: HashMap<String, ResolvedPredicate>,
+}
+
+impl SpecRegistry {
+    pub fn new() -> SpecRegistry {
+        SpecRegistry::default()
+    }
+
+    /// Registers a predicate for specs referring to it by the path `name`.
+    pub fn add_predicate<F>(&mut self, name: &str, predicate: F)
+    where
+        F: Fn(&[Element], &[PredicateLiteral]) -> Result<(), String> + Send + Sync + 'static,
+    {
+        let name: String = name.split_whitespace().collect();
+        self.predicates.insert(name, Box::new(predicate));
+    }
+
+    /// Replaces the templates of this registry by a specification.
+    ///
+    /// The spec is checked like `template_spec!` does, all predicates must be registered.
+    /// If there are errors, the templates stay unchanged.
+    pub fn load(&mut self, source: &str) -> Result<(), Vec<SpecError>> {
+        let (templates, mut errors) = load_spec(source);
+        let resolved = RefCell::new(HashMap::new());
+        let check_predicate = |predicate: &str| {
+            let reference = parse_predicate(predicate)?;
+            let predicate_ref = resolve(&reference, &self.predicates)?;
+            resolved
+                .borrow_mut()
+                .insert(predicate.to_string(), predicate_ref);
+            Ok(())
+        };
+        errors.append(&mut check_spec(&templates, &check_predicate));
+        if !errors.is_empty() {
+            sort_errors(&mut errors);
+            return Err(errors);
+        }
+        self.templates = templates;
+        self.resolved = resolved.into_inner();
+        Ok(())
+    }
+
+    /// Like `load`, reading the spec from a file.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), Vec<SpecError>> {
+        match fs::read_to_string(path) {
+            Ok(source) => self.load(&source),
+            Err(error) => Err(vec![SpecError::new(
+                0,
+                format!("could not read {}: {}", path.display(), error),
+            )]),
+        }
+    }
+
+    /// All templates of the specification.
+    pub fn templates(&self) -> &[SpecTemplate] {
+        &self.templates
+    }
+
+    /// An authoring guide for the templates, in Markdown.
+    pub fn reference_markdown(&self) -> String {
+        reference_markdown(&self.templates)
+    }
+
+    /// An authoring guide for the templates, in HTML.
+    pub fn reference_html(&self) -> String {
+        reference_html(&self.templates)
+    }
+
+    /// Get the specification of a specific template, if it exists.
+    pub fn spec_of(&self, name: &str) -> Option<&SpecTemplate> {
+        let name = name.trim().to_lowercase();
+        self.templates
+            .iter()
+            .find(|t| t.accepted_names().contains(&name))
+    }
+
+    /// Parses a template like `try_parse_template` of `template_spec!` does.
+    pub fn parse_template<'e>(
+        &self,
+        template: &'e Template,
+    ) -> Result<DynamicTemplate<'e>, TemplateError> {
+        let name = extract_plain_text(&template.name).trim().to_lowercase();
+        let spec = match self.spec_of(&name) {
+            Some(spec) => spec,
+            None => {
+                return Err(TemplateError {
+                    template: None,
+                    attribute: None,
+                    message: format!("unknown template `{}`", name),
+                    position: template.position.clone(),
+                })
+            }
+        };
+
+        let mut attributes = HashMap::new();
+        for attribute in &spec.attributes {
+            let value = self
+                .parse_attribute(attribute, &template.content)
+                .map_err(|message| TemplateError {
+                    template: Some(spec.identifier.clone()),
+                    attribute: Some(attribute.identifier.clone()),
+                    message: format!("{}: {}", spec.identifier, message),
+                    position: template.position.clone(),
+                })?;
+            if let Some(value) = value {
+                attributes.insert(attribute.identifier.clone(), value);
+            }
+        }
+
+        let given: Vec<&str> = spec
+            .attributes
+            .iter()
+            .filter(|attribute| is_given(attribute, &template.content))
+            .map(|attribute| attribute.identifier.as_str())
+            .collect();
+        if let Some((attribute, message)) = constraint_error(spec, &given) {
+            return Err(TemplateError {
+                template: Some(spec.identifier.clone()),
+                attribute,
+                message: format!("{}: {}", spec.identifier, message),
+                position: template.position.clone(),
+            });
+        }
+
+        Ok(DynamicTemplate {
+            identifier: spec.identifier.clone(),
+            attributes,
+            unknown: unknown_arguments(spec, &template.content),
+        })
+    }
+
+    fn check_predicate(&self, attribute: &SpecAttribute, value: &[Element]) -> Result<(), String> {
+        let predicate = self
+            .resolved
+            .get(&attribute.predicate)
+            .ok_or_else(|| format!("unknown predicate {:?}!", attribute.predicate))?;
+        self.evaluate(predicate, value).map_err(|cause| {
+            format!(
+                "`{}` does not satisfy {}: {}",
+                attribute.identifier, attribute.predicate, cause
+            )
+        })
+    }
+
+    /// Checks content like the predicate library of `template_spec!` does.
+    fn evaluate(&self, predicate: &ResolvedPredicate, root: &[Element]) -> Result<(), String> {
+        match *predicate {
+            ResolvedPredicate::OnlyText => {
+                for element in root {
+                    match *element {
+                        Element::Text(_) | Element::Comment(_) => (),
+                        _ => {
+                            return Err(format!(
+                                "only text is allowed here, found {}!",
+                                element.get_variant_name()
+                            ))
+                        }
+                    }
+                }
+            }
+            ResolvedPredicate::NoTemplates => {
+                if root.iter().any(|e| matches!(*e, Element::Template(_))) {
+                    return Err("templates are not allowed here!".into());
+                }
+            }
+            ResolvedPredicate::NoBlockElements => {
+                for element in root {
+                    match *element {
+                        Element::Paragraph(_)
+                        | Element::Heading(_)
+                        | Element::List(_)
+                        | Element::ListItem(_)
+                        | Element::Table(_)
+                        | Element::TableRow(_)
+                        | Element::TableCell(_)
+                        | Element::Gallery(_) => {
+                            return Err(format!(
+                                "block elements are not allowed here, found {}!",
+                                element.get_variant_name()
+                            ))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            ResolvedPredicate::NoHeadings => {
+                if root.iter().any(|e| matches!(*e, Element::Heading(_))) {
+                    return Err("headings are not allowed here!".into());
+                }
+            }
+            ResolvedPredicate::MaxLength(max) => {
+                let length = extract_plain_text(root).chars().count();
+                if length > max {
+                    return Err(format!(
+                        "text is {} characters long, at most {} are allowed!",
+                        length, max
+                    ));
+                }
+            }
+            ResolvedPredicate::Matches(ref pattern) => {
+                let text = extract_plain_text(root);
+                if !matches_pattern(text.trim(), pattern)? {
+                    return Err(format!("{:?} does not match {:?}!", text.trim(), pattern));
+                }
+            }
+            ResolvedPredicate::OnlyKinds(ref kinds) => {
+                for element in root {
+                    let kind = element.get_variant_name();
+                    if !kinds.iter().any(|k| k.eq_ignore_ascii_case(kind)) {
+                        return Err(format!(
+                            "{} is not allowed here, only {}!",
+                            kind,
+                            kinds.join(", ")
+                        ));
+                    }
+                }
+            }
+            ResolvedPredicate::AllOf(ref predicates) => {
+                for predicate in predicates {
+                    self.evaluate(predicate, root)?;
+                }
+            }
+            ResolvedPredicate::AnyOf(ref predicates) => {
+                let mut causes = vec![];
+                for predicate in predicates {
+                    match self.evaluate(predicate, root) {
+                        Ok(()) => return Ok(()),
+                        Err(cause) => causes.push(cause),
+                    }
+                }
+                return Err(format!(
+                    "none of the alternatives hold: {}",
+                    causes.join(" / ")
+                ));
+            }
+            ResolvedPredicate::Not(ref predicate, ref cause) => {
+                if self.evaluate(predicate, root).is_ok() {
+                    return Err(cause.clone());
+                }
+            }
+            ResolvedPredicate::Registered(ref name, ref args) => {
+                return self.predicates[name](root, args);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_attribute<'e>(
+        &self,
+        attribute: &SpecAttribute,
+        content: &'e [Element],
+    ) -> Result<Option<AttributeValue<'e>>, String> {
+        let names = attribute.accepted_names();
+        if attribute.repeated {
+            let args = find_numbered_args(content, &names);
+            let too_many = attribute.max_count.map(|max| args.len() > max);
+            if args.len() < attribute.min_count() || too_many == Some(true) {
+                return Err(format!(
+                    "`{}` has {} values, which is not allowed",
+                    attribute.identifier,
+                    args.len()
+                ));
+            }
+            let mut values = vec![];
+            for arg in args {
+                if let Element::TemplateArgument(ref arg) = *arg {
+                    self.check_predicate(attribute, &arg.value)?;
+                    values.push(convert(attribute, &arg.value)?);
+                }
+            }
+            return Ok(Some(AttributeValue::List(values)));
+        }
+
+        let all = find_all_args(content, &names, attribute.position);
+        if all.len() > 1 {
+            return Err(format!(
+                "`{}` is given multiple times",
+                attribute.identifier
+            ));
+        }
+        match find_positional_arg(content, &names, attribute.position) {
+            Some(Element::TemplateArgument(ref arg)) => {
+                self.check_predicate(attribute, &arg.value)?;
+                convert(attribute, &arg.value).map(Some)
+            }
+            _ if attribute.priority == SpecPriority::Required => {
+                Err(format!("`{}` is missing", attribute.identifier))
+            }
+            _ => Ok(attribute
+                .default
+                .as_ref()
+                .map(|d| default_value(attribute, d))),
+        }
+    }
+}
+
+fn lowercase_names(attribute: &SpecAttribute) -> Vec<String> {
+    attribute
+        .names
+        .iter()
+        .map(|n| n.trim().to_lowercase())
+        .collect()
+}
+
+/// Converts attribute content according to the attribute type.
+fn convert<'e>(
+    attribute: &SpecAttribute,
+    content: &'e [Element],
+) -> Result<AttributeValue<'e>, String> {
+    let value = match attribute.value_type {
+        SpecType::Elements => AttributeValue::Elements(Cow::Borrowed(content)),
+        SpecType::Text => AttributeValue::Text(parse_text(content)?),
+        SpecType::Integer => AttributeValue::Integer(parse_integer(content)?),
+        SpecType::Boolean => AttributeValue::Boolean(parse_boolean(content)?),
+        SpecType::Formula => AttributeValue::Text(parse_formula(content)?),
+        SpecType::File => AttributeValue::Text(parse_file_name(content)?),
+        SpecType::Enum => {
+            let values: Vec<String> = attribute.values.iter().map(|v| v.to_lowercase()).collect();
+            let values: Vec<&str> = values.iter().map(|v| v.as_str()).collect();
+            AttributeValue::Text(parse_enum(content, &values)?)
+        }
+    };
+    Ok(value)
+}
+
+/// The value of a default, which is checked when the spec is loaded.
+fn default_value<'e>(attribute: &SpecAttribute, default: &str) -> AttributeValue<'e> {
+    let value = default.trim();
+    match attribute.value_type {
+        SpecType::Elements => AttributeValue::Elements(Cow::Owned(parse_fragment(default))),
+        SpecType::Integer => AttributeValue::Integer(value.parse().unwrap_or_default()),
+        SpecType::Boolean => AttributeValue::Boolean(boolean_value(value).unwrap_or_default()),
+        SpecType::Enum => AttributeValue::Text(value.to_lowercase()),
+        SpecType::Text | SpecType::Formula | SpecType::File => AttributeValue::Text(value.into()),
+    }
+}
+
+/// Wether an attribute is given by an argument, not by its default.
+fn is_given(attribute: &SpecAttribute, content: &[Element]) -> bool {
+    let names = attribute.accepted_names();
+    if attribute.repeated {
+        !find_numbered_args(content, &names).is_empty()
+    } else {
+        find_positional_arg(content, &names, attribute.position).is_some()
+    }
+}
+
+/// The first violated `requires`, `conflicts_with` or `one_of` constraint of a template,
+/// given the identifiers of the given attributes, with the attribute it belongs to.
+fn constraint_error(spec: &SpecTemplate, given: &[&str]) -> Option<(Option<String>, String)> {
+    for attribute in &spec.attributes {
+        if !given.contains(&attribute.identifier.as_str()) {
+            continue;
+        }
+        let id = &attribute.identifier;
+        if let Some(required) = attribute
+            .requires
+            .iter()
+            .find(|r| !given.contains(&r.as_str()))
+        {
+            let message = format!("`{}` requires `{}`", id, required);
+            return Some((Some(id.clone()), message));
+        }
+        if let Some(other) = attribute
+            .conflicts_with
+            .iter()
+            .find(|c| given.contains(&c.as_str()))
+        {
+            let message = format!("`{}` conflicts with `{}`", id, other);
+            return Some((Some(id.clone()), message));
+        }
+    }
+    for group in &spec.one_of {
+        let found: Vec<String> = group
+            .iter()
+            .filter(|id| given.contains(&id.as_str()))
+            .map(|id| format!("`{}`", id))
+            .collect();
+        if found.len() != 1 {
+            let group: Vec<String> = group.iter().map(|id| format!("`{}`", id)).collect();
+            let found = match found.len() {
+                0 => "none".to_string(),
+                _ => found.join(", "),
+            };
+            let message = format!(
+                "exactly one of {} is needed, found {}",
+                group.join(", "),
+                found
+            );
+            return Some((None, message));
+        }
+    }
+    None
+}
+
+fn unknown_arguments(spec: &SpecTemplate, content: &[Element]) -> Vec<(String, Option<String>)> {
+    let mut known = vec![];
+    let mut prefixes = vec![];
+    let mut suggested = vec![];
+    for attribute in &spec.attributes {
+        if attribute.repeated {
+            prefixes.append(&mut attribute.accepted_names());
+            continue;
+        }
+        suggested.append(&mut lowercase_names(attribute));
+        known.append(&mut attribute.accepted_names());
+        if let Some(position) = attribute.position {
+            known.push(position.to_string());
+        }
+    }
+    unknown_args(content, &known, &prefixes)
+        .into_iter()
+        .map(|(name, _)| {
+            let suggestion = suggest_name(&name, &suggested, &prefixes);
+            (name, suggestion)
+        })
+        .collect()
+}
@@ -0,0 +1,276 @@
+//! MediaWiki [TemplateData](https://www.mediawiki.org/wiki/Extension:TemplateData),
+//! the template documentation read by VisualEditor.
+//!
+//! `TemplateSpec::template_data` of `template_spec!` exports a specification in this format,
+//! `TemplateData::to_spec` brings existing TemplateData under control of a specification.
+
+use mwparser_utils_meta::check::{check_default, is_identifier, RESERVED_NAMES};
+pub use mwparser_utils_meta::write_spec;
+use mwparser_utils_meta::{SpecAttribute, SpecFormat, SpecPriority, SpecTemplate, SpecType};
+use serde::de::{Deserialize, Deserializer};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The TemplateData of a template, to be serialized to JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateData {
+    #[serde(default, deserialize_with = "interface_text")]
+    pub description: String,
+    /// Parameters by their primary name.
+    pub params: BTreeMap<String, TemplateDataParam>,
+    /// Primary names of the parameters, in the order of the specification.
+    #[serde(default, rename = "paramOrder")]
+    pub param_order: Vec<String>,
+    /// `inline` or `block`, or a custom format string.
+    #[serde(default)]
+    pub format: String,
+}
+
+/// A template parameter described by TemplateData.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateDataParam {
+    /// A short name for editors.
+    #[serde(
+        default,
+        deserialize_with = "interface_text",
+        skip_serializing_if = "String::is_empty"
+    )]
+    pub label: String,
+    #[serde(default, deserialize_with = "interface_text")]
+    pub description: String,
+    /// One of the TemplateData types, like `content`, `line` or `number`.
+    #[serde(rename = "type", default = "unknown_type")]
+    pub param_type: String,
+    #[serde(default)]
+    pub required: bool,
+    /// Other names (or the position) of this parameter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Value of this parameter if it is missing, as wikitext.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(
+        default,
+        rename = "suggestedvalues",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub suggested_values: Vec<String>,
+}
+
+/// Descriptions and labels are plain text or translations by language code.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InterfaceText {
+    Plain(String),
+    Translated(BTreeMap<String, String>),
+}
+
+/// Reads an interface text, preferring the english translation.
+fn interface_text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Option::<InterfaceText>::deserialize(deserializer)? {
+        Some(InterfaceText::Plain(text)) => text,
+        Some(InterfaceText::Translated(mut translations)) => translations
+            .remove("en")
+            .or_else(|| translations.into_iter().next().map(|(_, text)| text))
+            .unwrap_or_default(),
+        None => String::new(),
+    })
+}
+
+fn unknown_type() -> String {
+    "unknown".into()
+}
+
+/// Replaces german special characters by their ASCII transcription.
+fn transcribe(c: char) -> Option<&'static str> {
+    match c {
+        'ä' | 'Ä' => Some("ae"),
+        'ö' | 'Ö' => Some("oe"),
+        'ü' | 'Ü' => Some("ue"),
+        'ß' => Some("ss"),
+        _ => None,
+    }
+}
+
+/// The words of a template or parameter name, transcribed to lowercase ASCII.
+fn name_words(name: &str) -> Vec<String> {
+    let mut words = vec![String::new()];
+    for c in name.chars() {
+        if let Some(transcription) = transcribe(c) {
+            words.last_mut().unwrap().push_str(transcription);
+        } else if c.is_ascii_alphanumeric() {
+            words.last_mut().unwrap().push(c.to_ascii_lowercase());
+        } else if !words.last().unwrap().is_empty() {
+            words.push(String::new());
+        }
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// A template identifier for a template name, like `ExampleBox` for `example box`.
+fn template_identifier(name: &str) -> String {
+    let mut identifier: String = name_words(name)
+        .iter()
+        .map(|word| word[..1].to_uppercase() + &word[1..])
+        .collect();
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        identifier.insert(0, 'T');
+    }
+    if !is_identifier(&identifier) {
+        identifier.push('_');
+    }
+    identifier
+}
+
+/// An attribute identifier for a parameter name, like `image_width` for `Image width`.
+fn attribute_identifier(name: &str) -> String {
+    let mut identifier = name_words(name).join("_");
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        identifier.insert_str(0, "arg_");
+    }
+    if !is_identifier(&identifier) || RESERVED_NAMES.contains(&identifier.as_str()) {
+        identifier.push('_');
+    }
+    identifier
+}
+
+impl TemplateData {
+    /// Reads TemplateData JSON, as found in `<templatedata>` tags of the wiki.
+    pub fn from_json(source: &str) -> Result<TemplateData, String> {
+        let source = source.trim();
+        let source = source
+            .strip_prefix("<templatedata>")
+            .and_then(|source| source.strip_suffix("</templatedata>"))
+            .unwrap_or(source);
+        serde_json::from_str(source).map_err(|error| error.to_string())
+    }
+
+    /// Converts this TemplateData of the template `name` to a specification,
+    /// with `predicate` for all attributes. The result can be written with `write_spec`.
+    ///
+    /// Parameters are converted in `param_order`, their first numeric name becomes the
+    /// position of the attribute. Defaults which do not fit the attribute type are left out.
+    pub fn to_spec(&self, name: &str, predicate: &str) -> SpecTemplate {
+        let mut order = self.param_order.clone();
+        for param in self.params.keys() {
+            if !order.contains(param) {
+                order.push(param.clone());
+            }
+        }
+
+        let mut attributes: Vec<SpecAttribute> = vec![];
+        for param_name in &order {
+            let mut attribute = match self.params.get(param_name) {
+                Some(param) => param.to_spec(param_name, predicate),
+                None => continue,
+            };
+            let identifier = attribute.identifier.clone();
+            let mut number = 1;
+            while attributes
+                .iter()
+                .any(|a| a.identifier == attribute.identifier)
+            {
+                number += 1;
+                attribute.identifier = format!("{}_{}", identifier, number);
+            }
+            attributes.push(attribute);
+        }
+
+        let format = if self.format == "block" || self.format.contains('\n') {
+            SpecFormat::Block
+        } else {
+            SpecFormat::Inline
+        };
+        SpecTemplate {
+            identifier: template_identifier(name),
+            description: self.description.clone(),
+            names: vec![name.trim().to_lowercase()],
+            deprecated_names: vec![],
+            format,
+            extends: None,
+            groups: vec![],
+            allowed_in: vec![],
+            forbidden_in: vec![],
+            allowed_children: None,
+            one_of: vec![],
+            max_per_document: None,
+            position: None,
+            attributes,
+            line: 0,
+        }
+    }
+}
+
+impl TemplateDataParam {
+    fn to_spec(&self, name: &str, predicate: &str) -> SpecAttribute {
+        let mut names: Vec<String> = vec![];
+        let mut position = None;
+        for name in Some(name)
+            .into_iter()
+            .chain(self.aliases.iter().map(|a| a.as_str()))
+        {
+            let name = name.trim().to_lowercase();
+            match name.parse::<usize>() {
+                Ok(number) if number > 0 && position.is_none() => position = Some(number),
+                _ if names.contains(&name) => (),
+                _ => names.push(name),
+            }
+        }
+        let identifier = match (names.first(), position) {
+            (Some(name), _) => attribute_identifier(name),
+            (None, position) => format!("arg_{}", position.unwrap_or_default()),
+        };
+
+        let mut values: Vec<String> = vec![];
+        for value in &self.suggested_values {
+            if !values.iter().any(|v| v.eq_ignore_ascii_case(value.trim())) {
+                values.push(value.trim().to_string());
+            }
+        }
+        let value_type = match self.param_type.as_str() {
+            "number" => SpecType::Integer,
+            "boolean" => SpecType::Boolean,
+            "wiki-file-name" => SpecType::File,
+            "line" | "string" if !values.is_empty() => SpecType::Enum,
+            "line" => SpecType::Text,
+            _ => SpecType::Elements,
+        };
+        if value_type != SpecType::Enum {
+            values.clear();
+        }
+
+        let description = if self.description.is_empty() {
+            self.label.clone()
+        } else {
+            self.description.clone()
+        };
+        let mut attribute = SpecAttribute {
+            identifier,
+            description,
+            names,
+            deprecated_names: vec![],
+            priority: if self.required {
+                SpecPriority::Required
+            } else {
+                SpecPriority::Optional
+            },
+            predicate: predicate.into(),
+            default: None,
+            position,
+            repeated: false,
+            min_count: None,
+            max_count: None,
+            value_type,
+            values,
+            requires: vec![],
+            conflicts_with: vec![],
+            line: 0,
+        };
+        attribute.default = self
+            .default
+            .clone()
+            .filter(|default| check_default(&attribute, default).is_ok());
+        attribute
+    }
+}
@@ -1,4 +1,6 @@
-use crate::util::{extract_plain_text, find_arg};
+use crate::path_methods;
+use crate::template_data;
+use crate::util::*;
 use mwparser_utils_derive::template_spec;
 
 const _SPEC: &str = include_str!("test_spec.yml");
@@ -8,3 +10,1181 @@ fn nop_pred<'s>(_: &'s [Element]) -> PredResult<'s> {
 }
 
 template_spec!("src/test_spec.yml");
+
+/// Returns the first template found in a document.
+fn first_template(root: &Element) -> Option<&Template> {
+    match *root {
+        Element::Template(ref template) => Some(template),
+        Element::Document(ref e) => e.content.iter().filter_map(first_template).next(),
+        Element::Paragraph(ref e) => e.content.iter().filter_map(first_template).next(),
+        _ => None,
+    }
+}
+
+fn parse(input: &str) -> Element {
+    mediawiki_parser::parse(input).expect("test input should parse")
+}
+
+/// Validates a document, returning the messages of all diagnostics.
+fn messages(input: &str) -> Vec<String> {
+    validate_document(&parse(input))
+        .into_iter()
+        .map(|d| d.message)
+        .collect()
+}
+
+#[test]
+fn parse_known_template() {
+    let root = parse("{{Example|title=foo|example=bar}}");
+    let template = first_template(&root).unwrap();
+    match parse_template(template) {
+        Some(KnownTemplate::Example(example)) => {
+            assert_eq!(extract_plain_text(example.title), "foo");
+            assert_eq!(extract_plain_text(example.example), "bar");
+            assert_eq!(example.present.len(), 2);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn missing_required_attribute() {
+    let root = parse("{{Example|title=foo}}");
+    let template = first_template(&root).unwrap();
+    assert_eq!(parse_template(template), None);
+    match try_parse_template(template) {
+        Err(ParseError::MissingAttribute {
+            template: ref name,
+            ref attribute,
+            ref names,
+            ref position,
+        }) => {
+            assert_eq!(name, "Example");
+            assert_eq!(attribute, "example");
+            assert_eq!(names, &vec!["example".to_string(), "2".to_string()]);
+            assert_eq!(position, &template.position);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn parse_errors() {
+    let root = parse("{{Unknown|foo}}");
+    let template = first_template(&root).unwrap();
+    let error = try_parse_template(template).unwrap_err();
+    assert_eq!(
+        error,
+        ParseError::UnknownTemplate {
+            name: "unknown".into(),
+            position: template.position.clone(),
+        }
+    );
+    assert_eq!(error.to_string(), "unknown template `unknown` at line 1");
+
+    // duplicates are only rejected by `try_parse_template`.
+    let root = parse("{{Example|foo|bar|title=baz}}");
+    let template = first_template(&root).unwrap();
+    assert!(parse_template(template).is_some());
+    match try_parse_template(template) {
+        Err(ParseError::DuplicateArgument { ref names, .. }) => {
+            assert_eq!(names, &vec!["1".to_string(), "title".to_string()])
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{Example|title={{X}}|example=bar}}");
+    let template = first_template(&root).unwrap();
+    assert!(parse_template(template).is_some());
+    match try_parse_template(template) {
+        Err(ParseError::PredicateFailed {
+            ref attribute,
+            ref predicate,
+            ref cause,
+            ..
+        }) => {
+            assert_eq!(attribute, "title");
+            assert_eq!(predicate, "all_of(no_templates, max_length(40))");
+            assert_eq!(cause, "templates are not allowed here!");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn spec_predicates() {
+    let root = parse("{{Example|example=bar}}");
+    let template = first_template(&root).unwrap();
+    let spec = spec_of("example").unwrap();
+    for attribute in &spec.attributes {
+        assert!((attribute.predicate)(&template.content).is_ok());
+    }
+}
+
+#[test]
+fn typed_attributes() {
+    let root = parse(
+        "{{Abbildung|datei=File:Circle.svg|width= 200 |align=Center|rahmen=ja|caption=A ''circle''}}",
+    );
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Figure(figure)) => {
+            assert_eq!(figure.file, "Circle.svg");
+            assert_eq!(figure.width, Some(200));
+            assert_eq!(figure.align, "center");
+            assert!(figure.framed);
+            assert_eq!(figure.caption, Some("A circle".into()));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{Equation|formula=<math>a^2 + b^2</math>}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Equation(equation)) => assert_eq!(equation.formula, "a^2 + b^2"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn conversion_errors() {
+    let root = parse("{{Figure|file=Circle.svg|width=wide}}");
+    match try_parse_template(first_template(&root).unwrap()) {
+        Err(ParseError::Conversion { ref error, .. }) => {
+            assert_eq!(error.attribute, "width");
+            assert_eq!(error.expected, AttributeType::Integer);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{Figure|file=Circle.svg|align=top}}");
+    match try_parse_template(first_template(&root).unwrap()) {
+        Err(ParseError::Conversion { ref error, .. }) => {
+            assert_eq!(error.expected, AttributeType::Enum)
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{Equation|formula=a^2}}");
+    assert!(try_parse_template(first_template(&root).unwrap()).is_err());
+    assert_eq!(parse_template(first_template(&root).unwrap()), None);
+}
+
+#[test]
+fn positional_arguments() {
+    let root = parse("{{Beispiel|foo|bar}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Example(example)) => {
+            assert_eq!(extract_plain_text(example.title), "foo");
+            assert_eq!(extract_plain_text(example.example), "bar");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // names take precedence over positions, later arguments override earlier ones.
+    let root = parse("{{Example|foo|title=baz|bar|2=qux}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Example(example)) => {
+            assert_eq!(extract_plain_text(example.title), "baz");
+            assert_eq!(extract_plain_text(example.example), "qux");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn resolve_template_arguments() {
+    let root = parse("{{T|a| 1 = b|c|Name=d}}");
+    let template = first_template(&root).unwrap();
+    let names: Vec<_> = template_arguments(&template.content)
+        .into_iter()
+        .map(|(name, arg)| (name, extract_plain_text(std::slice::from_ref(arg))))
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            ("1".to_string(), "b".to_string()),
+            ("2".to_string(), "c".to_string()),
+            ("name".to_string(), "d".to_string()),
+        ]
+    );
+    let arg = find_positional_arg(&template.content, &[], Some(2)).unwrap();
+    assert_eq!(extract_plain_text(std::slice::from_ref(arg)), "c");
+}
+
+#[test]
+fn default_values() {
+    let root = parse("{{Figure|file=Circle.svg}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Figure(figure)) => {
+            assert_eq!(figure.width, None);
+            assert_eq!(figure.align, "center");
+            assert!(!figure.framed);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{Example|example=bar}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Example(example)) => {
+            assert_eq!(extract_plain_text(example.title), "Beispiel");
+            assert_eq!(example.present.len(), 1);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let spec = spec_of("figure").unwrap();
+    assert_eq!(spec.attributes[2].default, Some("center".into()));
+}
+
+#[test]
+fn repeated_attributes() {
+    let root = parse("{{Liste|item2=b|type=ol|item1=a|item10=c}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::List(list)) => {
+            let items: Vec<_> = list.items.iter().map(|i| extract_plain_text(i)).collect();
+            assert_eq!(items, vec!["a", "b", "c"]);
+            assert_eq!(list.kind, "ol");
+            assert_eq!(list.present.len(), 4);
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{List|type=ol}}");
+    match try_parse_template(first_template(&root).unwrap()) {
+        Err(ParseError::WrongCount {
+            ref attribute,
+            min,
+            max,
+            found,
+            ..
+        }) => {
+            assert_eq!(attribute, "items");
+            assert_eq!((min, max, found), (1, Some(5), 0));
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{List|item1=a|item2=b|item3=c|item4=d|item5=e|item6=f}}");
+    assert!(try_parse_template(first_template(&root).unwrap()).is_err());
+
+    // only digits make a number, `item+1` is not `item1`.
+    let root = parse("{{List|item1=a|item+1=b|item1b=c}}");
+    let list = parse_template(first_template(&root).unwrap()).unwrap();
+    let unknown: Vec<_> = list.unknown().iter().map(|arg| arg.name.as_str()).collect();
+    assert_eq!(unknown, vec!["item+1", "item1b"]);
+}
+
+#[test]
+fn unknown_arguments() {
+    let root = parse("{{Example|titel=foo|baz|bar|x|iten2=a}}");
+    let known = parse_template(first_template(&root).unwrap()).unwrap();
+    let unknown: Vec<_> = known
+        .unknown()
+        .iter()
+        .map(|arg| (arg.name.as_str(), arg.suggestion.as_deref()))
+        .collect();
+    assert_eq!(
+        unknown,
+        vec![("titel", Some("title")), ("3", None), ("iten2", None)]
+    );
+
+    let root = parse("{{List|iten2=a|item1=b|typ=ol}}");
+    let known = parse_template(first_template(&root).unwrap()).unwrap();
+    let suggestions: Vec<_> = known
+        .unknown()
+        .iter()
+        .map(|arg| arg.suggestion.clone().unwrap())
+        .collect();
+    assert_eq!(suggestions, vec!["item2", "type"]);
+    assert_eq!(edit_distance("titel", "title"), 1);
+}
+
+#[test]
+fn validate_documents() {
+    let root =
+        parse("{{Example|titel=foo|example={{Unknown}}}}\n\n{{Figure|file=x.png|width=wide}}");
+    let diagnostics: Vec<_> = validate_document(&root)
+        .into_iter()
+        .map(|d| (d.severity, d.message))
+        .collect();
+    assert_eq!(
+        diagnostics,
+        vec![
+            (
+                Severity::Warning,
+                "Example: unknown argument `titel`, did you mean `title`?".to_string()
+            ),
+            (
+                Severity::Error,
+                "Figure: `width` is not a valid Integer value: \"wide\" is not an integer! at line 3"
+                    .to_string()
+            ),
+        ]
+    );
+
+    // unknown and deprecated names are reported for templates which fail to parse.
+    assert_eq!(
+        messages("{{Example|exampel=bar}}"),
+        vec![
+            "Example is missing `example` (as example, 2) at line 1",
+            "Example: unknown argument `exampel`, did you mean `example`?",
+        ]
+    );
+    assert_eq!(
+        messages("{{Figure|file=x.svg|width=wide|captoin=x|Unterschrift=y}}"),
+        vec![
+            "Figure: `width` is not a valid Integer value: \"wide\" is not an integer! at line 1",
+            "Figure: argument name `Unterschrift` is deprecated, use `caption`",
+            "Figure: unknown argument `captoin`, did you mean `caption`?",
+        ]
+    );
+
+    // predicates are checked for the whole attribute content.
+    let root = parse("{{Example|title='''{{X}}'''|example=bar}}");
+    let template = first_template(&root).unwrap();
+    assert!(try_parse_template(template).is_ok());
+    let diagnostics = validate_template(template);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Example: `title` does not satisfy all_of(no_templates, max_length(40)): templates are not allowed here!"
+    );
+    assert_eq!(diagnostics[0].position.start.col, 20);
+
+    // predicate failures are reported next to other errors of the template.
+    let other = parse("{{Example|title={{X}}}}");
+    let diagnostics = validate_template(first_template(&other).unwrap());
+    let found: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.message.as_str(), d.position.start.col))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Example is missing `example` (as example, 2) at line 1", 1),
+            (
+                "Example: `title` does not satisfy all_of(no_templates, max_length(40)): \
+                 templates are not allowed here!",
+                17
+            ),
+        ]
+    );
+
+    let root = mark_invalid_templates(root, ()).unwrap();
+    assert_eq!(first_template(&root), None);
+    match root {
+        Element::Document(ref doc) => match doc.content[..] {
+            [Element::Error(ref error)] => assert!(error.message.contains("no_templates")),
+            ref other => panic!("unexpected content: {:?}", other),
+        },
+        ref other => panic!("unexpected result: {:?}", other),
+    }
+
+    // templates missing from the specification are kept, their content is checked.
+    let root = mark_invalid_templates(parse("{{Unknown|{{Figure|width=wide}}}}"), ()).unwrap();
+    let template = first_template(&root).unwrap();
+    assert_eq!(extract_plain_text(&template.name), "Unknown");
+    match template.content[..] {
+        [Element::TemplateArgument(ref arg)] => match arg.value[..] {
+            [Element::Error(ref error)] => assert!(error.message.contains("missing `file`")),
+            ref other => panic!("unexpected content: {:?}", other),
+        },
+        ref other => panic!("unexpected content: {:?}", other),
+    }
+}
+
+#[test]
+fn collect_predicate_failures() {
+    let root = parse("{{X}} and '''{{Y}}''' or ''{{Z}}''");
+    let content = match root {
+        Element::Document(ref doc) => &doc.content,
+        ref other => panic!("unexpected result: {:?}", other),
+    };
+    let error = always(content, &no_templates).unwrap_err();
+    assert_eq!(error.tree.unwrap().get_position().start.col, 1);
+
+    let failures = always_collect(content, &no_templates);
+    let found: Vec<_> = failures
+        .iter()
+        .map(|f| (f.position.start.col, f.path.len()))
+        .collect();
+    assert_eq!(found, vec![(1, 1), (14, 2), (28, 2)]);
+    match *failures[1].path[1] {
+        Element::Formatted(ref formatted) => {
+            assert_eq!(formatted.markup, mediawiki_parser::MarkupType::Bold)
+        }
+        ref other => panic!("unexpected path: {:?}", other),
+    }
+}
+
+/// Content of the first paragraph (or the document) of some wikitext.
+fn parse_content(input: &str) -> Vec<Element> {
+    match parse(input) {
+        Element::Document(mut doc) => match doc.content.pop() {
+            Some(Element::Paragraph(par)) if doc.content.is_empty() => par.content,
+            Some(other) => {
+                doc.content.push(other);
+                doc.content
+            }
+            None => vec![],
+        },
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn predicate_library() {
+    let text = parse_content("some text <!-- comment -->");
+    let bold = parse_content("some '''bold''' text");
+    let template = parse_content("a {{Template}}");
+    let heading = parse_content("== Heading ==\ntext");
+
+    assert!(only_text(&text).is_ok());
+    assert!(only_text(&bold).is_err());
+    assert!(no_templates(&bold).is_ok());
+    assert_eq!(
+        no_templates(&template).unwrap_err().tree,
+        Some(&template[1])
+    );
+    assert!(no_block_elements(&bold).is_ok());
+    assert!(no_block_elements(&heading).is_err());
+    assert!(no_headings(&heading).is_err());
+    assert!(max_length(&bold, 14).is_ok());
+    assert!(max_length(&bold, 13).is_err());
+    assert!(matches(&parse_content(" 42 "), "[0-9]+").is_ok());
+    assert!(matches(&parse_content("42a"), "[0-9]+").is_err());
+    assert!(matches(&text, "(").is_err());
+    assert!(only_kinds(&bold, &["text", "formatted"]).is_ok());
+    assert!(only_kinds(&template, &["text"]).is_err());
+
+    assert!(all_of(&bold, &[&no_templates, &no_headings]).is_ok());
+    assert!(all_of(&bold, &[&no_templates, &only_text]).is_err());
+    assert!(any_of(&bold, &[&only_text, &no_templates]).is_ok());
+    assert!(any_of(&template, &[&only_text, &no_templates]).is_err());
+    assert!(not(&template, &no_templates, "a template is required!").is_ok());
+    let error = not(&text, &no_templates, "a template is required!").unwrap_err();
+    assert_eq!(error.cause, "a template is required!");
+
+    let root = parse("{{Figure|file=a.png|caption=\n== Heading ==\n}}");
+    match try_parse_template(first_template(&root).unwrap()) {
+        Err(ParseError::PredicateFailed { ref predicate, .. }) => {
+            assert_eq!(predicate, "no_block_elements")
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn parameterized_predicates() {
+    let spec = spec_of("example").unwrap();
+    let title = &spec.attributes[0];
+    assert_eq!(title.predicate_name, "all_of(no_templates, max_length(40))");
+    let long = parse_content(&"x".repeat(41));
+    assert_eq!(
+        (title.predicate)(&long).unwrap_err().cause,
+        "text is 41 characters long, at most 40 are allowed!"
+    );
+    assert!((title.predicate)(&parse_content("short")).is_ok());
+
+    let root = parse(&format!("{{{{Example|{}|bar}}}}", "x".repeat(41)));
+    match try_parse_template(first_template(&root).unwrap()) {
+        Err(ParseError::PredicateFailed { ref attribute, .. }) => assert_eq!(attribute, "title"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn runtime_registry() {
+    use crate::registry::*;
+    use std::path::Path;
+
+    let mut registry = SpecRegistry::new();
+    let errors = registry
+        .load_file(Path::new("src/test_spec.yml"))
+        .unwrap_err();
+    assert_eq!(errors.len(), 17);
+    // attributes of groups are reported once, at their definition.
+    assert_eq!(errors[0].line, 5);
+    assert_eq!(
+        errors[0].message,
+        "\"hint\": unknown predicate \"nop_pred\"!"
+    );
+    // the predicate library is built in.
+    assert_eq!(errors[1].line, 25);
+
+    for name in &["nop_pred", "crate :: test :: nop_pred"] {
+        registry.add_predicate(name, |_, _| Ok(()));
+    }
+    registry.load_file(Path::new("src/test_spec.yml")).unwrap();
+    assert_eq!(registry.templates().len(), 8);
+    assert_eq!(registry.spec_of(" Abbildung").unwrap().identifier, "Figure");
+    assert_eq!(registry.spec_of("Bild").unwrap().identifier, "Figure");
+
+    let root = parse("{{Abbildung|datei=File:Circle.svg|width=200|capton=x}}");
+    let figure = registry
+        .parse_template(first_template(&root).unwrap())
+        .unwrap();
+    assert_eq!(figure.identifier, "Figure");
+    assert_eq!(figure.attributes.len(), 4);
+    assert_eq!(
+        figure.attributes["file"],
+        AttributeValue::Text("Circle.svg".into())
+    );
+    assert_eq!(figure.attributes["width"], AttributeValue::Integer(200));
+    assert_eq!(
+        figure.attributes["align"],
+        AttributeValue::Text("center".into())
+    );
+    assert_eq!(figure.attributes["framed"], AttributeValue::Boolean(false));
+    assert_eq!(
+        figure.unknown,
+        vec![("capton".to_string(), Some("caption".to_string()))]
+    );
+
+    let root = parse("{{Liste|item1=a|item2=b}}");
+    let list = registry
+        .parse_template(first_template(&root).unwrap())
+        .unwrap();
+    match list.attributes["items"] {
+        AttributeValue::List(ref items) => assert_eq!(items.len(), 2),
+        ref other => panic!("unexpected value: {:?}", other),
+    }
+
+    let root = parse(&format!("{{{{Example|{}}}}}", "x".repeat(41)));
+    let error = registry
+        .parse_template(first_template(&root).unwrap())
+        .unwrap_err();
+    assert_eq!(error.attribute, Some("title".into()));
+    assert_eq!(
+        error.to_string(),
+        "Example: `title` does not satisfy all_of(no_templates, max_length(40)): \
+         text is 41 characters long, at most 40 are allowed! at line 1"
+    );
+}
+
+#[test]
+fn runtime_predicate_arguments() {
+    use crate::registry::*;
+
+    let spec = |predicate: &str| {
+        format!(
+            "- id: Quote\n  names: [quote]\n  description: A quote.\n  format: inline\n  \
+             attributes:\n    - id: text\n      names: [text]\n      priority: required\n      \
+             predicate: '{}'\n      description: The quote.\n",
+            predicate
+        )
+    };
+    let mut registry = SpecRegistry::new();
+    registry.add_predicate("min_words", |root, args| match args {
+        [PredicateLiteral::Integer(min)] => {
+            let words = extract_plain_text(root).split_whitespace().count() as u64;
+            if words < *min {
+                return Err(format!("{} words are too few!", words));
+            }
+            Ok(())
+        }
+        _ => Err("min_words takes one integer!".into()),
+    });
+    let errors = registry
+        .load(&spec("all_of(min_words(2.5), unknown)"))
+        .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "\"text\": unknown predicate \"unknown\"!"
+    );
+    let errors = registry.load(&spec("min_words(no_templates)")).unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "\"text\": `min_words` takes only integers, floats, strings and booleans at runtime!"
+    );
+
+    registry
+        .load(&spec(
+            "all_of(min_words(2), not(min_words(4), \"too many words!\"))",
+        ))
+        .unwrap();
+    let check = |input: &str| {
+        registry
+            .parse_template(first_template(&parse(input)).unwrap())
+            .map(|_| ())
+            .map_err(|error| error.message)
+    };
+    assert_eq!(check("{{quote|text=two words}}"), Ok(()));
+    assert_eq!(
+        check("{{quote|text=one}}"),
+        Err(
+            "Quote: `text` does not satisfy all_of(min_words(2), not(min_words(4), \
+             \"too many words!\")): 1 words are too few!"
+                .into()
+        )
+    );
+    assert_eq!(
+        check("{{quote|text=a few more words}}"),
+        Err(
+            "Quote: `text` does not satisfy all_of(min_words(2), not(min_words(4), \
+             \"too many words!\")): too many words!"
+                .into()
+        )
+    );
+}
+
+#[test]
+fn rebuild_templates() {
+    let root = parse("{{Abbildung|breite=200|datei=File:Circle.svg|align=left|caption=A circle}}");
+    let figure = parse_template(first_template(&root).unwrap()).unwrap();
+    assert_eq!(
+        figure.to_wikitext(),
+        "{{figure|file=File:Circle.svg|width=200|align=left|caption=A circle}}"
+    );
+
+    let rebuilt = figure.to_template();
+    match rebuilt {
+        Element::Template(ref template) => {
+            let reparsed = parse_template(template).unwrap();
+            assert_eq!(reparsed.to_wikitext(), figure.to_wikitext());
+        }
+        ref other => panic!("unexpected element: {:?}", other),
+    }
+
+    let root = parse("{{Liste|type=ol|item2=b|item1=a|extra=c}}");
+    let list = parse_template(first_template(&root).unwrap()).unwrap();
+    assert_eq!(
+        list.to_wikitext(),
+        "{{list|item1=a|item2=b|type=ol|extra=c}}"
+    );
+
+    let root = parse("{{Example|example=Some ''text''}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Example(example)) => {
+            assert_eq!(example.to_wikitext(), "{{example|example=Some ''text''}}");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let source = "{{figure|file=File:x.svg|caption=A ''circle'' <nowiki>|</nowiki> here}}";
+    let root = parse(source);
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Figure(mut figure)) => {
+            assert_eq!(figure.caption, Some("A circle | here".into()));
+            assert_eq!(figure.to_wikitext(), source);
+            figure.caption = Some("a|b=c}}".into());
+            let root = parse(&figure.to_wikitext());
+            match parse_template(first_template(&root).unwrap()) {
+                Some(KnownTemplate::Figure(reparsed)) => {
+                    assert_eq!(reparsed.file, "x.svg");
+                    assert_eq!(reparsed.caption, figure.caption);
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let root = parse("{{Gleichung|formel=<math>x^2</math>}}");
+    let equation = parse_template(first_template(&root).unwrap()).unwrap();
+    assert_eq!(
+        equation.to_wikitext(),
+        "{{equation|formula=<math>x^2</math>}}"
+    );
+}
+
+#[test]
+fn wikitext_round_trip() {
+    let captions = ["a|b=c", "a<b>c", "tail}", "{head", "it's ''great''"];
+    for caption in &captions {
+        let template = Figure::builder()
+            .file("x.svg")
+            .caption(caption)
+            .into_template()
+            .unwrap();
+        let root = parse(&to_wikitext(&[template]));
+        match parse_template(first_template(&root).unwrap()) {
+            Some(KnownTemplate::Figure(figure)) => {
+                assert_eq!(figure.caption, Some(caption.to_string()))
+            }
+            other => panic!("unexpected result for {:?}: {:?}", caption, other),
+        }
+    }
+
+    // text is checked like it would be read from wikitext.
+    for caption in &["a {{b}} c", "x [[Link]] y"] {
+        match Figure::builder().file("x.svg").caption(caption).build() {
+            Err(ParseError::Conversion { ref error, .. }) => assert_eq!(error.attribute, "caption"),
+            other => panic!("unexpected result for {:?}: {:?}", caption, other),
+        }
+    }
+    let fixed = Figure::builder()
+        .file("x.svg")
+        .caption("x [[Link]] y")
+        .caption("x Link y");
+    assert!(fixed.build().is_ok());
+}
+
+#[test]
+fn build_templates() {
+    let builder = Figure::builder()
+        .caption("A circle")
+        .file("File:Circle.svg")
+        .width(200);
+    let figure = builder.build().unwrap();
+    assert_eq!(figure.file, "Circle.svg");
+    assert_eq!(figure.width, Some(200));
+    assert_eq!(figure.align, "center");
+    assert_eq!(figure.caption, Some("A circle".into()));
+    assert_eq!(
+        builder.into_template().map(|t| to_wikitext(&[t])),
+        Ok("{{figure|file=File:Circle.svg|width=200|caption=A circle}}".into())
+    );
+
+    let piped = Figure::builder().file("x.svg").caption("a|b=c");
+    let wikitext = piped.into_template().map(|t| to_wikitext(&[t])).unwrap();
+    assert_eq!(
+        wikitext,
+        "{{figure|file=x.svg|caption=<nowiki>a|b=c</nowiki>}}"
+    );
+    match parse_template(first_template(&parse(&wikitext)).unwrap()) {
+        Some(KnownTemplate::Figure(figure)) => assert_eq!(figure.caption, Some("a|b=c".into())),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let list = List::builder()
+        .items(text_content("a"))
+        .items(text_content("b"))
+        .kind("ol");
+    assert_eq!(list.build().unwrap().items.len(), 2);
+    assert_eq!(
+        list.into_template().map(|t| to_wikitext(&[t])),
+        Ok("{{list|item1=a|item2=b|type=ol}}".into())
+    );
+
+    match Example::builder().title(text_content("foo")).build() {
+        Err(ParseError::MissingAttribute { ref attribute, .. }) => {
+            assert_eq!(attribute, "example")
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let too_long = Example::builder()
+        .title(text_content(&"x".repeat(41)))
+        .example(text_content("bar"));
+    match too_long.into_template() {
+        Err(ParseError::PredicateFailed { ref attribute, .. }) => assert_eq!(attribute, "title"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match Equation::builder().formula("x^2").build() {
+        Ok(equation) => assert_eq!(equation.formula, "x^2"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn export_template_data() {
+    let figure = spec_of("figure").unwrap().template_data();
+    assert_eq!(
+        figure.param_order,
+        vec!["file", "width", "align", "framed", "caption"]
+    );
+    assert_eq!(figure.format, "block");
+    let json = serde_json::to_value(&figure.params["file"]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "description": "The image file.",
+            "type": "wiki-file-name",
+            "required": true,
+            "aliases": ["datei"],
+        })
+    );
+    let json = serde_json::to_value(&figure.params["align"]).unwrap();
+    assert_eq!(json["default"], "center");
+    assert_eq!(
+        json["suggestedvalues"],
+        serde_json::json!(["left", "center", "right"])
+    );
+
+    let example = spec_of("example").unwrap().template_data();
+    assert_eq!(example.params["title"].aliases, vec!["1"]);
+    assert_eq!(example.params["title"].param_type, "content");
+    assert_eq!(example.format, "block");
+
+    let list = spec_of("list").unwrap().template_data();
+    assert_eq!(
+        list.param_order,
+        vec!["item1", "item2", "item3", "item4", "item5", "type"]
+    );
+    assert!(list.params["item1"].required);
+    assert!(!list.params["item2"].required);
+    let json = serde_json::to_string(&list).unwrap();
+    let parsed: template_data::TemplateData = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, list);
+}
+
+#[test]
+fn import_template_data() {
+    use mwparser_utils_meta::{check_spec, load_spec, SpecType};
+
+    let source = r#"<templatedata>{
+        "description": {"de": "Ein Zitat.", "en": "A quote."},
+        "params": {
+            "1": {"label": "Text", "type": "content", "required": true, "aliases": ["Text"]},
+            "quelle": {"description": "Source of the quote.", "type": "line", "aliases": ["source"]},
+            "type": {"type": "string", "suggestedvalues": ["Block", "Inline", "inline"], "default": "block"},
+            "größe": {"type": "number", "default": "auto"}
+        },
+        "paramOrder": ["1", "quelle", "type", "größe"],
+        "format": "block"
+    }</templatedata>"#;
+    let data = template_data::TemplateData::from_json(source).unwrap();
+    let spec = data.to_spec("Zitat Box", "nop_pred");
+    assert_eq!(spec.identifier, "ZitatBox");
+    assert_eq!(spec.names, vec!["zitat box"]);
+    assert_eq!(spec.description, "A quote.");
+
+    let text = &spec.attributes[0];
+    assert_eq!(text.identifier, "text");
+    assert_eq!(text.names, vec!["text"]);
+    assert_eq!(text.position, Some(1));
+    assert_eq!(text.description, "Text");
+    let types: Vec<_> = spec.attributes.iter().map(|a| a.value_type).collect();
+    assert_eq!(
+        types,
+        vec![
+            SpecType::Elements,
+            SpecType::Text,
+            SpecType::Enum,
+            SpecType::Integer
+        ]
+    );
+    let kind = &spec.attributes[2];
+    assert_eq!(kind.identifier, "type_");
+    assert_eq!(kind.values, vec!["Block", "Inline"]);
+    assert_eq!(kind.default, Some("block".into()));
+    assert_eq!(spec.attributes[3].identifier, "groesse");
+    assert_eq!(spec.attributes[3].default, None);
+
+    let yaml = template_data::write_spec(std::slice::from_ref(&spec));
+    let (templates, errors) = load_spec(&yaml);
+    assert_eq!(errors, vec![]);
+    assert_eq!(check_spec(&templates, &|_| Ok(())), vec![]);
+    assert_eq!(templates[0].attributes.len(), 4);
+    assert_eq!(templates[0].attributes[2].values, kind.values);
+
+    // exported specs are imported with the same attributes, deprecated names become aliases.
+    let (templates, _) = load_spec(_SPEC);
+    let figure = spec_of("figure").unwrap().template_data();
+    let imported = figure.to_spec("figure", "nop_pred");
+    for (original, imported) in templates[1].attributes.iter().zip(&imported.attributes) {
+        assert_eq!(imported.identifier, original.identifier);
+        assert_eq!(imported.names, original.accepted_names());
+        assert_eq!(imported.value_type, original.value_type);
+        assert_eq!(imported.default, original.default);
+    }
+}
+
+#[test]
+fn reference_documentation() {
+    let markdown = spec_meta::reference_markdown();
+    assert!(markdown.starts_with("# Templates\n\n## Example\n\nA mathematical example.\n"));
+    assert!(markdown.contains(
+        "| `title` | `title`, `1` | optional, default `''Beispiel''` \
+         | `all_of(no_templates, max_length(40))` | A name for this example. |\n"
+    ));
+    assert!(
+        markdown.contains("| `items` | `item` (deprecated: `punkt`) | required, 1 to 5 values |")
+    );
+    assert!(markdown.contains("```\n{{list\n|item1=...\n|item2=...\n|type=ul\n}}\n```\n"));
+
+    let html = spec_meta::reference_html();
+    assert!(html.contains("<section id=\"Equation\">\n<h2>Equation</h2>\n"));
+    assert!(html.contains("<pre>{{equation\n|formula=&lt;math&gt;x^2&lt;/math&gt;\n}}</pre>"));
+
+    // the example invocations fit the specification.
+    let (templates, _) = mwparser_utils_meta::load_spec(_SPEC);
+    for template in &templates {
+        let invocation = mwparser_utils_meta::reference::example_invocation(template);
+        let root = parse(&invocation);
+        let parsed = parse_template(first_template(&root).unwrap());
+        assert!(parsed.is_some(), "{}", invocation);
+    }
+}
+
+#[test]
+fn json_schema() {
+    let schema: serde_json::Value = serde_json::from_str(spec_meta::json_schema()).unwrap();
+    assert_eq!(schema["oneOf"].as_array().unwrap().len(), 8);
+    let figure_schema = &schema["definitions"]["Figure"];
+    assert_eq!(figure_schema["properties"]["format"]["const"], "Block");
+    assert_eq!(
+        figure_schema["properties"]["align"]["allOf"][0]["enum"],
+        serde_json::json!(["left", "center", "right"])
+    );
+    assert_eq!(
+        figure_schema["properties"]["width"]["allOf"][0]["oneOf"][1]["type"],
+        "null"
+    );
+    let items = &schema["definitions"]["List"]["properties"]["items"]["allOf"][0];
+    assert_eq!(items["minItems"], 1);
+    assert_eq!(items["maxItems"], 5);
+
+    // serialized templates have exactly the properties of the schema.
+    let root = parse("{{Abbildung|datei=File:Circle.svg|width=200|capton=x}}");
+    let figure = parse_template(first_template(&root).unwrap()).unwrap();
+    let json = serde_json::to_value(&figure).unwrap();
+    let object = json["Figure"].as_object().unwrap();
+    let mut keys: Vec<&String> = object.keys().collect();
+    keys.sort();
+    let required: Vec<&str> = figure_schema["required"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| key.as_str().unwrap())
+        .collect();
+    assert_eq!(keys, required);
+    assert_eq!(
+        object["identifier"],
+        figure_schema["properties"]["identifier"]["const"]
+    );
+    assert_eq!(
+        object["names"],
+        figure_schema["properties"]["names"]["const"]
+    );
+    assert_eq!(object["width"], 200);
+    assert_eq!(object["caption"], serde_json::Value::Null);
+    assert_eq!(object["unknown"][0]["suggestion"], "caption");
+}
+
+#[test]
+fn deprecated_names() {
+    let root =
+        parse("{{Bild|datei=File:Circle.svg|Unterschrift=A circle}}\n\n{{Liste|punkt1=a|item2=b}}");
+    let diagnostics = validate_document(&root);
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Figure: template name `bild` is deprecated, use `figure`",
+            "Figure: argument name `Unterschrift` is deprecated, use `caption`",
+            "List: argument name `punkt1` is deprecated, use `item1`",
+        ]
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    assert_eq!(diagnostics[1].position.start.col, 30);
+
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Figure(figure)) => {
+            assert_eq!(figure.caption, Some("A circle".into()));
+            assert!(figure.unknown.is_empty());
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let migrated = migrate_deprecated_names(root, ()).unwrap();
+    let templates: Vec<String> = match migrated {
+        Element::Document(ref doc) => doc
+            .content
+            .iter()
+            .map(|e| to_wikitext(std::slice::from_ref(e)))
+            .collect(),
+        ref other => panic!("unexpected result: {:?}", other),
+    };
+    assert_eq!(
+        templates,
+        vec![
+            "{{figure|datei=File:Circle.svg|caption=A circle}}",
+            "{{Liste|item1=a|item2=b}}"
+        ]
+    );
+    assert_eq!(validate_document(&migrated), vec![]);
+}
+
+#[test]
+fn canonicalize_templates() {
+    let canonical = |input: &str| {
+        let root = spec_meta::canonicalize_templates(parse(input), ()).unwrap();
+        to_wikitext(&[root])
+    };
+    assert_eq!(
+        canonical("{{ Beispiel |example=bar| Title =Foo}}"),
+        "{{example|title=Foo|example=bar}}"
+    );
+    assert_eq!(
+        canonical("{{Beispiel|foo|bar|extra| Titel =x}}"),
+        "{{example|title=foo|example=bar|3=extra|Titel=x}}"
+    );
+    assert_eq!(
+        canonical("{{liste|type=ol|item2=b|Punkt1=a}}"),
+        "{{list|item1=a|item2=b|type=ol}}"
+    );
+    assert_eq!(
+        canonical("{{Bild|Unterschrift=A {{liste|item1=a}}|breite=200|datei=x.svg}}"),
+        "{{figure|file=x.svg|width=200|caption=A {{list|item1=a}}}}"
+    );
+    // named arguments override positional ones, overridden arguments are dropped.
+    assert_eq!(
+        canonical("{{Example|title=named|positional|example=x}}"),
+        "{{example|title=named|example=x}}"
+    );
+    assert_eq!(
+        canonical("{{Example|a|example=x|3=b|c|3=d}}"),
+        "{{example|title=a|example=x|3=d}}"
+    );
+    assert_eq!(canonical("{{Other|a=b}}"), "{{Other|a=b}}");
+}
+
+#[test]
+fn placement_rules() {
+    assert_eq!(
+        messages("{{Example|example={{List|item1={{Equation|formula=<math>x</math>}}}}}}"),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        messages("{{Example|example=''{{Example|example=x}}''}}"),
+        vec!["Example: not allowed inside `Example`"]
+    );
+    assert_eq!(
+        messages("{{Equation|formula=<math>x</math>}}"),
+        vec!["Equation: only allowed inside `Example`, `List`"]
+    );
+    // unknown templates are not checked and do not count as parents.
+    assert_eq!(
+        messages("{{List|item1={{Other|{{Example|example=x}}}}}}"),
+        vec!["List: `Example` is not allowed as a child"]
+    );
+
+    let root = parse("{{Example|example={{Example|example=x}}}}");
+    let diagnostics = validate_document(&root);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert_eq!(diagnostics[0].position.start.col, 19);
+}
+
+#[test]
+fn format_rules() {
+    assert_eq!(
+        messages("A {{Term|word}} in a paragraph.\n\n{{Figure|file=x.svg}}\n"),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        messages("{{Term|a\n* list}}"),
+        vec!["Term: block content in inline template: block elements are not allowed here, found Paragraph!",
+            "Term: block content in inline template: block elements are not allowed here, found ListItem!"]
+    );
+    assert_eq!(
+        messages("Some text {{Figure|file=x.svg}} and more."),
+        vec!["Figure: block template inside a paragraph"]
+    );
+    assert_eq!(
+        messages("{{Term|{{Example|example=x}}}}"),
+        vec!["Example: box template inside inline template `Term`"]
+    );
+
+    let root = parse("{{Term|a\n\n== heading ==\n}}");
+    let diagnostics = validate_document(&root);
+    assert_eq!(diagnostics.len(), 3);
+    assert!(diagnostics[2].message.ends_with("found Heading!"));
+    assert_eq!(diagnostics[2].position.start.line, 3);
+}
+
+#[test]
+fn attribute_constraints() {
+    let error = |input: &str| -> Option<String> {
+        match try_parse_template(first_template(&parse(input)).unwrap()) {
+            Ok(_) => None,
+            Err(error) => Some(error.to_string()),
+        }
+    };
+    assert_eq!(error("{{Exercise|task|solution=x|proof=p|proof2=q}}"), None);
+    assert_eq!(error("{{Aufgabe|task|solution-link=Page}}"), None);
+    assert_eq!(
+        error("{{Exercise|task|solution=x|proof2=q}}"),
+        Some("Exercise: `proof2` requires `proof` at line 1".into())
+    );
+    assert_eq!(
+        error("{{Exercise|task|solution-link=Page|proof=p}}"),
+        Some("Exercise: `proof` conflicts with `solution_link` at line 1".into())
+    );
+    assert_eq!(
+        error("{{Exercise|task}}"),
+        Some(
+            "Exercise: exactly one of `solution`, `solution_link` is needed, found none at line 1"
+                .into()
+        )
+    );
+    let root = parse("{{Exercise|task|solution=x|solution-link=Page}}");
+    match try_parse_template(first_template(&root).unwrap()) {
+        Err(ParseError::OneOf { found, .. }) => {
+            assert_eq!(found, vec!["solution", "solution_link"])
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(parse_template(first_template(&root).unwrap()), None);
+
+    let mut registry = crate::registry::SpecRegistry::new();
+    registry.add_predicate("nop_pred", |_, _| Ok(()));
+    let spec = std::fs::read_to_string("src/test_spec.yml").unwrap();
+    let groups = &spec[..spec.find("- id: Example").unwrap()];
+    let exercise = &spec[spec.find("- id: Exercise").unwrap()..];
+    registry.load(&format!("{}{}", groups, exercise)).unwrap();
+    let error = registry
+        .parse_template(first_template(&parse("{{Exercise|task|proof2=q|solution=x}}")).unwrap())
+        .unwrap_err();
+    assert_eq!(error.attribute, Some("proof2".into()));
+    assert_eq!(error.message, "Exercise: `proof2` requires `proof`");
+}
+
+#[test]
+fn document_occurrences() {
+    assert_eq!(
+        messages("<!-- header -->\n{{Navigation}}\n\nSome text.\n\n{{Sources}}\n"),
+        Vec::<String>::new()
+    );
+    // headings nest the content following them.
+    assert_eq!(
+        messages(
+            "{{Navigation}}\n\n== Intro ==\nSome text.\n\n=== Details ===\nMore.\n\n{{Sources}}"
+        ),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        messages("== Intro ==\n{{Navigation}}\n\n== Outro ==\n{{Sources}}\n\nMore text."),
+        vec![
+            "Navigation: must be at the start of the document",
+            "Sources: must be at the end of the document",
+        ]
+    );
+    assert_eq!(
+        messages("Some text.\n\n{{Navigation}}\n\n{{Sources}}\n\nMore text."),
+        vec![
+            "Navigation: must be at the start of the document",
+            "Sources: must be at the end of the document",
+        ]
+    );
+    assert_eq!(
+        messages("{{Navigation}}\n\n{{Example|example={{Navigation}}}}"),
+        vec![
+            "Navigation: at most 1 allowed per document",
+            "Navigation: must be at the start of the document",
+        ]
+    );
+
+    let root = parse("{{Navigation}}\n\ntext\n\n{{Navigation}}");
+    let diagnostics = validate_occurrences(&root);
+    assert_eq!(diagnostics, validate_document(&root));
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|d| d.position.start.line == 5));
+}
+
+#[test]
+fn attribute_groups_and_extends() {
+    // attributes of groups are expanded before code generation.
+    let exercise = spec_of("exercise").unwrap();
+    assert_eq!(exercise.attributes[0].identifier, "hint");
+    let root = parse("{{Navigation|tipp=Read this first.}}");
+    match parse_template(first_template(&root).unwrap()) {
+        Some(KnownTemplate::Navigation(navigation)) => {
+            assert_eq!(
+                extract_plain_text(navigation.hint.unwrap()),
+                "Read this first."
+            )
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -15,7 +15,7 @@ fn convert_template_list_rec(mut root: Element, _settings: ()) -> TResult {
         if ["list", "liste"].contains(&template_name.as_str()) {
             let mut list_content = vec![];
 
-            let list_type = if let Some(&Element::TemplateArgument(ref arg)) =
+            let list_type = if let Some(Element::TemplateArgument(arg)) =
                 find_arg(&template.content, &["type".into()])
             {
                 extract_plain_text(&arg.value).to_lowercase()
@@ -25,7 +25,7 @@ fn convert_template_list_rec(mut root: Element, _settings: ()) -> TResult {
 
             let item_kind = match list_type.trim() {
                 "ol" | "ordered" => ListItemKind::Ordered,
-                "ul" | _ => ListItemKind::Unordered,
+                _ => ListItemKind::Unordered,
             };
 
             for child in template.content.drain(..) {
@@ -62,7 +62,7 @@ fn convert_template_list_rec(mut root: Element, _settings: ()) -> TResult {
 }
 
 /// Normalize math formulas with texvccheck
-pub fn normalize_math_formulas(mut root: Element, checker: &TexChecker) -> TResult {
+pub fn normalize_math_formulas(mut root: Element, checker: &dyn TexChecker) -> TResult {
     if let Element::Formatted(ref mut formatted) = root {
         if formatted.markup == MarkupType::Math {
             match check_formula(&formatted.content, &formatted.position, checker) {
@@ -78,7 +78,7 @@ pub fn normalize_math_formulas(mut root: Element, checker: &TexChecker) -> TResu
 }
 
 /// Check a Tex formula, return normalized version or error
-fn check_formula(content: &[Element], position: &Span, checker: &TexChecker) -> Element {
+fn check_formula(content: &[Element], position: &Span, checker: &dyn TexChecker) -> Element {
     if content.len() != 1 {
         return Element::Error(Error {
             message: "A formula must have exactly one content element!".into(),
@@ -1,11 +1,12 @@
 //! Common utilities for mfnf tools.
 
 use mediawiki_parser::*;
+use regex::Regex;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
 
 /// Convert a filename to a make-friedly format.
 pub fn filename_to_make(input: &str) -> String {
@@ -87,9 +88,9 @@ pub struct CachedTexChecker {
 }
 
 impl CachedTexChecker {
-    pub fn new(path: &PathBuf, size: usize) -> CachedTexChecker {
+    pub fn new(path: &Path, size: usize) -> CachedTexChecker {
         CachedTexChecker {
-            texvccheck_path: path.clone(),
+            texvccheck_path: path.to_path_buf(),
             max_size: size,
             cache: Mutex::new(HashMap::with_capacity(size)),
         }
@@ -152,3 +153,581 @@ pub fn find_arg<'a>(content: &'a [Element], names: &[String]) -> Option<&'a Elem
     }
     None
 }
+
+/// Returns all template arguments of a list with the names MediaWiki binds them to.
+///
+/// Unnamed arguments are numbered starting from 1, in order of appearance.
+/// Names are trimmed and lowercase.
+fn bound_arguments(content: &[Element]) -> Vec<(String, &Element)> {
+    let mut counter = 1;
+    let mut result = vec![];
+    for child in content {
+        if let Element::TemplateArgument(ref e) = *child {
+            let mut name = e.name.trim().to_lowercase();
+            if name.is_empty() {
+                name = counter.to_string();
+                counter += 1;
+            }
+            result.push((name, child));
+        }
+    }
+    result
+}
+
+/// Returns the template arguments of a list with the names MediaWiki binds them to.
+///
+/// Unnamed arguments are numbered starting from 1, in order of appearance. Names are
+/// trimmed and lowercase. If multiple arguments are bound to the same name, like in
+/// `{{T|a|1=b}}`, only the last one is kept.
+pub fn template_arguments(content: &[Element]) -> Vec<(String, &Element)> {
+    let mut result: Vec<(String, &Element)> = vec![];
+    for (name, child) in bound_arguments(content) {
+        result.retain(|(n, _)| *n != name);
+        result.push((name, child));
+    }
+    result
+}
+
+/// Returns all template arguments bound to one of `names` (lowercase) or to the
+/// unnamed argument at `position`, including arguments overridden by later ones.
+pub fn find_all_args<'a>(
+    content: &'a [Element],
+    names: &[String],
+    position: Option<usize>,
+) -> Vec<&'a Element> {
+    let position = position.map(|p| p.to_string());
+    bound_arguments(content)
+        .into_iter()
+        .filter(|(name, _)| names.contains(name) || Some(name) == position.as_ref())
+        .map(|(_, arg)| arg)
+        .collect()
+}
+
+/// Returns the template argument bound to one of `names` (lowercase) or to
+/// the unnamed argument at `position` (starting at 1).
+///
+/// Arguments are resolved like `template_arguments` does.
+/// A matching name takes precedence over the position.
+pub fn find_positional_arg<'a>(
+    content: &'a [Element],
+    names: &[String],
+    position: Option<usize>,
+) -> Option<&'a Element> {
+    let arguments = template_arguments(content);
+    let position = position.map(|p| p.to_string());
+    arguments
+        .iter()
+        .find(|(name, _)| names.contains(name))
+        .or_else(|| {
+            arguments
+                .iter()
+                .find(|(name, _)| Some(name) == position.as_ref())
+        })
+        .map(|(_, arg)| *arg)
+}
+
+/// Returns the template arguments named by one of `prefixes` (lowercase) followed
+/// by a number, like `item1` to `itemN`, ordered by this number.
+///
+/// Arguments are resolved like `template_arguments` does.
+pub fn find_numbered_args<'a>(content: &'a [Element], prefixes: &[String]) -> Vec<&'a Element> {
+    let mut numbered = vec![];
+    for (name, arg) in template_arguments(content) {
+        if let Some(number) = prefixes.iter().find_map(|p| name_number(&name, p)) {
+            numbered.push((number, arg));
+        }
+    }
+    numbered.sort_by_key(|(number, _)| *number);
+    numbered.into_iter().map(|(_, arg)| arg).collect()
+}
+
+/// Returns the number of a name consisting of `prefix` followed by ASCII digits.
+pub fn name_number(name: &str, prefix: &str) -> Option<usize> {
+    let number = name.strip_prefix(prefix)?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Returns the template arguments which are neither bound to one of `names` (lowercase,
+/// including positions as numbers) nor named by one of `prefixes` followed by a number.
+///
+/// Unnamed arguments are numbered like `template_arguments` does.
+pub fn unknown_args<'a>(
+    content: &'a [Element],
+    names: &[String],
+    prefixes: &[String],
+) -> Vec<(String, &'a Element)> {
+    bound_arguments(content)
+        .into_iter()
+        .filter(|(name, _)| {
+            !names.contains(name) && !prefixes.iter().any(|p| name_number(name, p).is_some())
+        })
+        .collect()
+}
+
+/// Number of single character insertions, deletions, substitutions or
+/// transpositions of adjacent characters needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance of the first i characters of a to the first j of b.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// Suggests the name closest to a misspelled argument `name`, if it is similar enough.
+///
+/// For a numbered name like `iten2`, `prefixes` are considered with the same number.
+pub fn suggest_name(name: &str, names: &[String], prefixes: &[String]) -> Option<String> {
+    let stem = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = &name[stem.len()..];
+    if stem.is_empty() {
+        // excess unnamed arguments are no typos.
+        return None;
+    }
+    let mut candidates: Vec<(usize, String)> = names
+        .iter()
+        .map(|n| (edit_distance(name, n), n.clone()))
+        .collect();
+    if !number.is_empty() {
+        for prefix in prefixes {
+            candidates.push((edit_distance(stem, prefix), format!("{}{}", prefix, number)));
+        }
+    }
+    // allow about one typo per three characters.
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Parses a piece of wikitext, like a template argument value.
+///
+/// A single paragraph is unwrapped, errors are returned as `Element::Error`.
+pub fn parse_fragment(source: &str) -> Vec<Element> {
+    let mut content = match mediawiki_parser::parse(source) {
+        Ok(Element::Document(document)) => document.content,
+        Ok(other) => vec![other],
+        Err(error) => {
+            return vec![Element::Error(Error {
+                position: Span::any(),
+                message: format!("could not parse {:?}: {}", source, error),
+            })]
+        }
+    };
+    if let [Element::Paragraph(_)] = content.as_slice() {
+        if let Some(Element::Paragraph(paragraph)) = content.pop() {
+            return paragraph.content;
+        }
+    }
+    content
+}
+
+/// Checks that `content` only consists of (formatted) text.
+fn check_plain_text(content: &[Element]) -> Result<(), String> {
+    for root in content {
+        match *root {
+            Element::Text(_) | Element::Comment(_) => (),
+            Element::Paragraph(ref e) => check_plain_text(&e.content)?,
+            Element::Formatted(ref e) if e.markup == MarkupType::Math => {
+                return Err("expected plain text, found a formula!".into());
+            }
+            Element::Formatted(ref e) => check_plain_text(&e.content)?,
+            ref e => {
+                return Err(format!(
+                    "expected plain text, found {}!",
+                    e.get_variant_name()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Converts an attribute value to plain text, rejecting any other markup.
+pub fn parse_text(content: &[Element]) -> Result<String, String> {
+    check_plain_text(content)?;
+    Ok(extract_plain_text(content).trim().to_string())
+}
+
+/// Converts an attribute value to an integer.
+pub fn parse_integer(content: &[Element]) -> Result<i64, String> {
+    let text = parse_text(content)?;
+    text.parse()
+        .map_err(|_| format!("{:?} is not an integer!", text))
+}
+
+/// Converts an attribute value to a boolean. Accepts english and german yes / no values.
+pub fn parse_boolean(content: &[Element]) -> Result<bool, String> {
+    let text = parse_text(content)?;
+    match text.to_lowercase().as_str() {
+        "true" | "yes" | "ja" | "1" => Ok(true),
+        "false" | "no" | "nein" | "0" => Ok(false),
+        _ => Err(format!("{:?} is not a boolean value!", text)),
+    }
+}
+
+/// Converts an attribute value to one of the (lowercase) allowed `values`.
+pub fn parse_enum(content: &[Element], values: &[&str]) -> Result<String, String> {
+    let text = parse_text(content)?.to_lowercase();
+    if values.contains(&text.as_str()) {
+        Ok(text)
+    } else {
+        Err(format!("{:?} is not one of {}!", text, values.join(", ")))
+    }
+}
+
+/// Returns the source of the single math formula an attribute value consists of.
+pub fn parse_formula(content: &[Element]) -> Result<String, String> {
+    let mut formula = None;
+    for root in content {
+        match *root {
+            Element::Formatted(ref e) if e.markup == MarkupType::Math && formula.is_none() => {
+                formula = Some(extract_plain_text(&e.content));
+            }
+            Element::Text(ref e) if e.text.trim().is_empty() => (),
+            Element::Paragraph(ref e) => return parse_formula(&e.content),
+            Element::Comment(_) => (),
+            _ => return Err("expected exactly one math formula!".into()),
+        }
+    }
+    formula.ok_or_else(|| "expected a math formula!".into())
+}
+
+/// Converts an attribute value to a file name, without a namespace prefix.
+pub fn parse_file_name(content: &[Element]) -> Result<String, String> {
+    let text = parse_text(content)?;
+    let name = match text.find(':') {
+        Some(index) => {
+            let prefix = text[..index].trim().to_lowercase();
+            if ["file", "datei", "image", "bild"].contains(&prefix.as_str()) {
+                text[index + 1..].trim()
+            } else {
+                &text
+            }
+        }
+        None => &text,
+    };
+    if name.is_empty() {
+        return Err("the file name is empty!".into());
+    }
+    if let Some(c) = name.chars().find(|c| "#<>[]|{}\n".contains(*c)) {
+        return Err(format!("{:?} is not allowed in file names!", c));
+    }
+    Ok(name.to_string())
+}
+
+/// Checks wether a regular expression matches the whole of `text`.
+///
+/// Compiled expressions are cached, an invalid `pattern` is an error.
+pub fn matches_pattern(text: &str, pattern: &str) -> Result<bool, String> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .expect("regex cache poisoned!");
+    if !cache.contains_key(pattern) {
+        let regex = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|error| format!("invalid pattern {:?}: {}", pattern, error))?;
+        cache.insert(pattern.to_string(), regex);
+    }
+    Ok(cache[pattern].is_match(text))
+}
+
+/// Reads a text value like the content of a template argument, to check it for markup.
+///
+/// Text which cannot be read as wikitext at all, like `a}`, is not markup
+/// and stays a single text element, `to_wikitext` puts it in `<nowiki>`.
+pub fn text_value_content(text: &str) -> Vec<Element> {
+    let content = parse_fragment(text);
+    match content.as_slice() {
+        [Element::Error(_)] => text_content(text),
+        _ => content,
+    }
+}
+
+/// A single text element without position, like it would be parsed from `text`.
+pub fn text_content(text: &str) -> Vec<Element> {
+    vec![Element::Text(Text {
+        position: Span::any(),
+        text: text.into(),
+    })]
+}
+
+/// A `<math>` formula without position.
+pub fn formula_content(formula: &str) -> Vec<Element> {
+    vec![Element::Formatted(Formatted {
+        position: Span::any(),
+        markup: MarkupType::Math,
+        content: text_content(formula),
+    })]
+}
+
+/// Builds a template element from a name and named arguments, without positions.
+pub fn build_template(name: &str, arguments: Vec<(String, Vec<Element>)>) -> Element {
+    Element::Template(Template {
+        position: Span::any(),
+        name: text_content(name),
+        content: arguments
+            .into_iter()
+            .map(|(name, value)| {
+                Element::TemplateArgument(TemplateArgument {
+                    position: Span::any(),
+                    name,
+                    value,
+                })
+            })
+            .collect(),
+    })
+}
+
+/// Serializes elements to wikitext.
+///
+/// The result parses to an equivalent tree, but may differ from the original source
+/// in whitespace and syntax variants. Errors are left out. Text in template arguments
+/// is put in `<nowiki>` where it could be read as markup.
+pub fn to_wikitext(content: &[Element]) -> String {
+    let mut result = String::new();
+    for element in content {
+        write_wikitext(element, &mut result);
+    }
+    result.trim_end_matches('\n').to_string()
+}
+
+fn tag_attributes(attributes: &[TagAttribute]) -> String {
+    attributes
+        .iter()
+        .map(|a| format!(" {}=\"{}\"", a.key, a.value))
+        .collect()
+}
+
+fn ensure_newline(result: &mut String) {
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+}
+
+fn markup_tags(markup: &MarkupType) -> (&'static str, &'static str) {
+    match *markup {
+        MarkupType::Bold => ("'''", "'''"),
+        MarkupType::Italic => ("''", "''"),
+        MarkupType::Math => ("<math>", "</math>"),
+        MarkupType::NoWiki => ("<nowiki>", "</nowiki>"),
+        MarkupType::StrikeThrough => ("<s>", "</s>"),
+        MarkupType::Underline => ("<u>", "</u>"),
+        MarkupType::Code => ("<code>", "</code>"),
+        MarkupType::Blockquote => ("<blockquote>", "</blockquote>"),
+        MarkupType::Preformatted => ("<pre>", "</pre>"),
+    }
+}
+
+/// Wether `text` could be read as markup inside a template argument.
+fn needs_nowiki(text: &str) -> bool {
+    ["|", "=", "{{", "}}", "[[", "]]", "''", "<"]
+        .iter()
+        .any(|markup| text.contains(markup))
+        || text.starts_with(['{', '\'', ']'])
+        || text.ends_with(['}', '\'', '['])
+}
+
+/// Writes a template argument value, with text which could be read as markup
+/// (like `|`, `=` or `}}`, ending the argument or the template) in `<nowiki>`.
+fn write_argument_value(content: &[Element], result: &mut String) {
+    let start = result.len();
+    for element in content {
+        match *element {
+            Element::Text(ref e) if needs_nowiki(&e.text) => {
+                result.push_str("<nowiki>");
+                result.push_str(&e.text);
+                result.push_str("</nowiki>");
+            }
+            Element::Formatted(ref e) => match e.markup {
+                MarkupType::Math | MarkupType::NoWiki | MarkupType::Preformatted => {
+                    write_wikitext(element, result)
+                }
+                _ => {
+                    let (open, close) = markup_tags(&e.markup);
+                    result.push_str(open);
+                    write_argument_value(&e.content, result);
+                    result.push_str(close);
+                }
+            },
+            Element::Paragraph(ref e) => {
+                write_argument_value(&e.content, result);
+                result.push_str("\n\n");
+            }
+            _ => write_wikitext(element, result),
+        }
+    }
+    let end = result.trim_end_matches('\n').len().max(start);
+    result.truncate(end);
+}
+
+fn write_wikitext(element: &Element, result: &mut String) {
+    match *element {
+        Element::Document(ref e) => {
+            for child in &e.content {
+                write_wikitext(child, result);
+            }
+        }
+        Element::Text(ref e) => result.push_str(&e.text),
+        Element::Formatted(ref e) => {
+            let (open, close) = markup_tags(&e.markup);
+            result.push_str(open);
+            result.push_str(&to_wikitext(&e.content));
+            result.push_str(close);
+        }
+        Element::Paragraph(ref e) => {
+            result.push_str(&to_wikitext(&e.content));
+            result.push_str("\n\n");
+        }
+        Element::Heading(ref e) => {
+            let marks = "=".repeat(e.depth);
+            ensure_newline(result);
+            result.push_str(&format!(
+                "{} {} {}\n",
+                marks,
+                to_wikitext(&e.caption),
+                marks
+            ));
+            for child in &e.content {
+                write_wikitext(child, result);
+            }
+        }
+        Element::Template(ref e) => {
+            result.push_str("{{");
+            result.push_str(&to_wikitext(&e.name));
+            for argument in &e.content {
+                result.push('|');
+                write_wikitext(argument, result);
+            }
+            result.push_str("}}");
+        }
+        Element::TemplateArgument(ref e) => {
+            if !e.name.is_empty() {
+                result.push_str(&e.name);
+                result.push('=');
+            }
+            write_argument_value(&e.value, result);
+        }
+        Element::InternalReference(ref e) => {
+            result.push_str("[[");
+            result.push_str(&to_wikitext(&e.target));
+            for option in &e.options {
+                result.push('|');
+                result.push_str(&to_wikitext(option));
+            }
+            if !e.caption.is_empty() {
+                result.push('|');
+                result.push_str(&to_wikitext(&e.caption));
+            }
+            result.push_str("]]");
+        }
+        Element::ExternalReference(ref e) => {
+            result.push('[');
+            result.push_str(&e.target);
+            if !e.caption.is_empty() {
+                result.push(' ');
+                result.push_str(&to_wikitext(&e.caption));
+            }
+            result.push(']');
+        }
+        Element::List(ref e) => {
+            ensure_newline(result);
+            for child in &e.content {
+                write_wikitext(child, result);
+            }
+        }
+        Element::ListItem(ref e) => {
+            let mark = match e.kind {
+                ListItemKind::Unordered => "*",
+                ListItemKind::Ordered => "#",
+                ListItemKind::Definition => ":",
+                ListItemKind::DefinitionTerm => ";",
+            };
+            ensure_newline(result);
+            result.push_str(&mark.repeat(e.depth));
+            result.push(' ');
+            for child in &e.content {
+                write_wikitext(child, result);
+            }
+            ensure_newline(result);
+        }
+        Element::Table(ref e) => {
+            ensure_newline(result);
+            result.push_str(&format!("{{|{}\n", tag_attributes(&e.attributes)));
+            if !e.caption.is_empty() {
+                result.push_str(&format!(
+                    "|+{} {}\n",
+                    tag_attributes(&e.caption_attributes),
+                    to_wikitext(&e.caption)
+                ));
+            }
+            for row in &e.rows {
+                write_wikitext(row, result);
+            }
+            result.push_str("|}\n");
+        }
+        Element::TableRow(ref e) => {
+            result.push_str(&format!("|-{}\n", tag_attributes(&e.attributes)));
+            for cell in &e.cells {
+                write_wikitext(cell, result);
+            }
+        }
+        Element::TableCell(ref e) => {
+            result.push(if e.header { '!' } else { '|' });
+            if !e.attributes.is_empty() {
+                result.push_str(&format!("{} |", tag_attributes(&e.attributes)));
+            }
+            result.push(' ');
+            result.push_str(&to_wikitext(&e.content));
+            result.push('\n');
+        }
+        Element::Comment(ref e) => {
+            result.push_str("<!--");
+            result.push_str(&e.text);
+            result.push_str("-->");
+        }
+        Element::HtmlTag(ref e) => {
+            result.push_str(&format!(
+                "<{}{}>{}</{}>",
+                e.name,
+                tag_attributes(&e.attributes),
+                to_wikitext(&e.content),
+                e.name
+            ));
+        }
+        Element::Gallery(ref e) => {
+            ensure_newline(result);
+            result.push_str(&format!("<gallery{}>\n", tag_attributes(&e.attributes)));
+            for child in &e.content {
+                result.push_str(&to_wikitext(std::slice::from_ref(child)));
+                result.push('\n');
+            }
+            result.push_str("</gallery>\n");
+        }
+        Element::Error(_) => (),
+    }
+}